@@ -0,0 +1,8429 @@
+//! Posts trending repos on GitHub to social media.
+//!
+//! The binary only parses the arguments and calls [`run`]. The parser of the trending page and
+//! the formatters of posts are public, to be reused by other tools:
+//!
+//! ```no_run
+//! # fn main() -> anyhow::Result<()> {
+//! # let html = String::new();
+//! let repos = rust_trending::parse_trending(html)?;
+//! let format = rust_trending::FormatConfig::default();
+//! for repo in &repos {
+//!     println!(
+//!         "{}",
+//!         rust_trending::make_toot(repo, rust_trending::DEFAULT_TEMPLATE, "", None, &format)
+//!     );
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap, HashSet},
+    convert::{Infallible, TryInto},
+    fs::File,
+    future::Future,
+    io::{self, BufReader, Read},
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use async_trait::async_trait;
+use atom_syndication::FixedDateTime;
+use atrium_api::{app::bsky, client::AtpServiceClient, com::atproto};
+use bytes::Bytes;
+use clap::Parser;
+use futures::future::{join_all, FutureExt, LocalBoxFuture};
+use once_cell::sync::{Lazy, OnceCell};
+use prometheus::{
+    register_histogram_vec, register_int_counter, register_int_counter_vec, register_int_gauge,
+    register_int_gauge_vec, Encoder, HistogramVec, IntCounter, IntCounterVec, IntGauge,
+    IntGaugeVec, TextEncoder,
+};
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use regex::Regex;
+use rusqlite::OptionalExtension;
+use scraper::Selector;
+use secp256k1::{KeyPair, SECP256K1};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use time::{
+    format_description::{
+        well_known::{Rfc2822, Rfc3339},
+        FormatItem,
+    },
+    macros::format_description,
+    Date, OffsetDateTime, Time,
+};
+use tracing::{debug, error, info, warn};
+use twitter_v2::{authorization::Oauth1aToken, TwitterApi};
+use unicode_segmentation::UnicodeSegmentation;
+use url::Url;
+
+const TWEET_LENGTH: usize = 280;
+const TOOT_LENGTH: usize = 500;
+const BLUESKY_POST_LENGTH: usize = 300;
+const DISCORD_POST_LENGTH: usize = 2000;
+const DISCORD_API_URL: &str = "https://discord.com/api/v10";
+const TELEGRAM_API_URL: &str = "https://api.telegram.org";
+const TELEGRAM_POST_LENGTH: usize = 4096;
+/// Nostr has no limit, but clients expect notes as short as a tweet.
+const NOSTR_POST_LENGTH: usize = 280;
+const MASTODON_FIXED_URL_LENGTH: usize = 23;
+/// Language detection is unreliable on shorter descriptions, which are never skipped.
+const DESCRIPTION_LANG_MIN_CHARS: usize = 30;
+const DESCRIPTION_LANG_MIN_CONFIDENCE: f64 = 0.5;
+/// Digests are marked posted for a bit longer than a day, so that the mark outlives the date.
+const DIGEST_TTL: usize = 2 * 24 * 60 * 60;
+const TIME_OF_DAY_FORMAT: &[FormatItem] = format_description!("[hour]:[minute]");
+/// Mastodon rejects statuses scheduled sooner than this.
+const MASTODON_MIN_SCHEDULE_DELAY: Duration = Duration::from_secs(5 * 60);
+const SMALL_COMMERCIAL_AT: &str = "﹫";
+const FETCH_RETRIES: u32 = 3;
+const FETCH_RETRY_DELAY: Duration = Duration::from_secs(1);
+const OG_IMAGE_RETRIES: u32 = 2;
+const REDIS_RECONNECT_RETRIES: u32 = 5;
+const REDIS_RECONNECT_DELAY: Duration = Duration::from_secs(1);
+const RATE_LIMIT_RETRIES: u32 = 2;
+const RATE_LIMIT_DEFAULT_DELAY: Duration = Duration::from_secs(10);
+const RATE_LIMIT_MAX_DELAY: Duration = Duration::from_secs(5 * 60);
+const REDDIT_TOKEN_URL: &str = "https://www.reddit.com/api/v1/access_token";
+const REDDIT_SUBMIT_URL: &str = "https://oauth.reddit.com/api/submit";
+/// Reddit tokens are renewed this long before they expire, so that they don't expire mid-request.
+const REDDIT_TOKEN_EXPIRY_MARGIN: Duration = Duration::from_secs(60);
+
+#[derive(Deserialize)]
+struct IntervalConfig {
+    post_ttl: usize,
+    fetch_interval: u64,
+    post_interval: u64,
+    /// Adds a random delay of up to this many seconds to each fetch interval, so that instances
+    /// don't poll GitHub at the same time.
+    #[serde(default)]
+    fetch_jitter_secs: u64,
+    /// Adds a random delay of up to this many seconds to each post interval.
+    #[serde(default)]
+    post_jitter_secs: u64,
+}
+
+/// `secs` plus a random delay of `0..=jitter_secs` seconds.
+fn jittered(secs: u64, jitter_secs: u64) -> Duration {
+    Duration::from_secs(secs + rand::thread_rng().gen_range(0..=jitter_secs))
+}
+
+/// Range of the trending page.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Since {
+    Daily,
+    Weekly,
+    Monthly,
+}
+
+impl Since {
+    fn as_str(self) -> &'static str {
+        match self {
+            Since::Daily => "daily",
+            Since::Weekly => "weekly",
+            Since::Monthly => "monthly",
+        }
+    }
+
+    /// e.g. "239 stars today" on the trending page.
+    fn period_label(self) -> &'static str {
+        match self {
+            Since::Daily => "today",
+            Since::Weekly => "this week",
+            Since::Monthly => "this month",
+        }
+    }
+}
+
+/// Format of numbers of stars.
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum StarFormat {
+    /// e.g. `49626`
+    #[default]
+    Plain,
+    /// With thousands separators, e.g. `49,626`
+    Grouped,
+}
+
+/// How `@` starting a mention is escaped on platforms which would notify the user.
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AtEscape {
+    /// Replaced with `at_replacement`, `﹫` by default.
+    #[default]
+    Replace,
+    /// Followed by a zero-width space, which renders as a normal `@`.
+    ZeroWidthSpace,
+}
+
+/// Formatting of posts, shared by every platform.
+#[derive(Deserialize, Default)]
+pub struct FormatConfig {
+    #[serde(default)]
+    pub star_format: StarFormat,
+    /// Put before the number of stars, `★` by default.
+    #[serde(default)]
+    pub star_symbol: Option<String>,
+    /// Put after truncated descriptions, ` ...` by default.
+    #[serde(default)]
+    pub truncation_suffix: Option<String>,
+    #[serde(default)]
+    pub at_escape: AtEscape,
+    /// Replaces `@` of mentions with `at_escape = "replace"`, `﹫` by default.
+    #[serde(default)]
+    pub at_replacement: Option<String>,
+}
+
+impl FormatConfig {
+    fn star_symbol(&self) -> &str {
+        self.star_symbol.as_deref().unwrap_or("★")
+    }
+
+    fn truncation_suffix(&self) -> &str {
+        self.truncation_suffix.as_deref().unwrap_or(" ...")
+    }
+
+    /// Put in place of `@` starting a mention.
+    fn escaped_at(&self) -> &str {
+        match self.at_escape {
+            AtEscape::Replace => self
+                .at_replacement
+                .as_deref()
+                .unwrap_or(SMALL_COMMERCIAL_AT),
+            AtEscape::ZeroWidthSpace => "@\u{200B}",
+        }
+    }
+
+    /// Number of stars with the symbol, e.g. `★49626`.
+    fn stars(&self, stars: usize) -> String {
+        format!(
+            "{}{}",
+            self.star_symbol(),
+            format_stars(stars, self.star_format)
+        )
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum LogFormat {
+    #[default]
+    Text,
+    /// One JSON object per line, for log collectors like Loki.
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => bail!("unknown log format `{}`, expected `text` or `json`", s),
+        }
+    }
+}
+
+fn init_logging(format: LogFormat) -> Result<()> {
+    // Already initialized when `run` is called again in the same process, like in tests
+    if tracing::dispatcher::has_been_set() {
+        return Ok(());
+    }
+    // Only errors are logged unless RUST_LOG is set, like env_logger
+    let filter = tracing_subscriber::EnvFilter::builder()
+        .with_default_directive(tracing::Level::ERROR.into())
+        .from_env_lossy();
+    let subscriber = tracing_subscriber::fmt().with_env_filter(filter);
+    match format {
+        LogFormat::Text => subscriber.try_init(),
+        LogFormat::Json => subscriber.json().try_init(),
+    }
+    .map_err(|error| anyhow!(error))
+    .context("While initializing logging")
+}
+
+fn default_languages() -> Vec<String> {
+    vec!["rust".to_string()]
+}
+
+fn default_since() -> Vec<Since> {
+    vec![Since::Daily]
+}
+
+#[derive(Deserialize)]
+struct TrendingConfig {
+    #[serde(default = "default_languages")]
+    languages: Vec<String>,
+    #[serde(default = "default_since")]
+    since: Vec<Since>,
+    /// Repos with less total stars than this are not posted.
+    min_stars: Option<usize>,
+    /// Repos that gained less stars than this during the range are not posted.
+    min_stars_period: Option<usize>,
+    /// Posted repos are posted again once they gained this many stars since the last post.
+    repost_on_star_delta: Option<usize>,
+    /// Repos without a description are not posted.
+    #[serde(default)]
+    skip_empty_description: bool,
+    /// ISO 639-3 codes like `eng`. Repos whose description is detected to be in another language
+    /// are not posted.
+    allowed_description_langs: Option<Vec<String>>,
+    /// At most this many repos are posted in a loop, leaving the rest to the next loops.
+    max_posts_per_run: Option<usize>,
+    /// At most this many repos of an author are posted in a loop, so that an organization with
+    /// many trending repos doesn't flood the timeline.
+    max_per_author_per_run: Option<usize>,
+    #[serde(default)]
+    order: PostOrder,
+}
+
+/// Order of posting the fetched repos.
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum PostOrder {
+    /// As ranked in the trending page.
+    #[default]
+    Trending,
+    StarsDesc,
+    StarsAsc,
+    Random,
+    /// Random, with the next repo chosen with probability proportional to its stars.
+    Weighted,
+    /// Like `Weighted`, by the stars gained during the trending range.
+    WeightedPeriod,
+}
+
+/// Sorts the repos in the order, keeping the trending order of repos with the same stars.
+/// Random orders differ between every loop and run.
+fn sort_repos(repos: &mut [Repo], order: PostOrder) {
+    match order {
+        PostOrder::Trending => {}
+        PostOrder::StarsDesc => repos.sort_by_key(|repo| std::cmp::Reverse(repo.stars)),
+        PostOrder::StarsAsc => repos.sort_by_key(|repo| repo.stars),
+        PostOrder::Random => repos.shuffle(&mut rand::thread_rng()),
+        PostOrder::Weighted | PostOrder::WeightedPeriod => {
+            let seed = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_nanos() as u64;
+            debug!(seed, "shuffling repos weighted by stars");
+            let mut rng = StdRng::seed_from_u64(seed);
+            if order == PostOrder::Weighted {
+                weighted_shuffle(repos, |repo| repo.stars, &mut rng);
+            } else {
+                weighted_shuffle(repos, |repo| repo.stars_period, &mut rng);
+            }
+        }
+    }
+}
+
+/// Shuffles the repos as if each next one was drawn from the rest with probability proportional
+/// to its weight, so that posting them in order until `max_posts_per_run` draws from those not
+/// posted yet. Repos without stars get the weight of one, to still be drawn sometimes.
+fn weighted_shuffle(repos: &mut [Repo], weight: impl Fn(&Repo) -> usize, rng: &mut impl Rng) {
+    // Sorting by u^(1/w) descending draws without replacement (Efraimidis and Spirakis)
+    let mut keys = repos
+        .iter()
+        .map(|repo| {
+            let weight = weight(repo).max(1) as f64;
+            (rng.gen::<f64>().powf(1.0 / weight), repo.clone())
+        })
+        .collect::<Vec<_>>();
+    keys.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+    for (repo, (_, shuffled)) in repos.iter_mut().zip(keys) {
+        *repo = shuffled;
+    }
+}
+
+fn default_bluesky_langs() -> Vec<String> {
+    vec!["en".to_string()]
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Detected language of the description, if it's long enough to be detected with confidence.
+fn detect_description_lang(description: &str) -> Option<whatlang::Lang> {
+    if description.chars().count() < DESCRIPTION_LANG_MIN_CHARS {
+        return None;
+    }
+    whatlang::detect(description)
+        .filter(|info| info.confidence() >= DESCRIPTION_LANG_MIN_CONFIDENCE)
+        .map(|info| info.lang())
+}
+
+/// Loosely checks a BCP 47 language tag like `en` or `zh-Hant-TW`.
+fn is_language_tag(tag: &str) -> bool {
+    let mut subtags = tag.split('-');
+    let primary = subtags.next().unwrap_or_default();
+    (2..=8).contains(&primary.len())
+        && primary.chars().all(|c| c.is_ascii_alphabetic())
+        && subtags.all(|subtag| {
+            (1..=8).contains(&subtag.len()) && subtag.chars().all(|c| c.is_ascii_alphanumeric())
+        })
+}
+
+/// Loosely checks a Bluesky login identifier, which is a handle like `alice.bsky.social`, a DID
+/// like `did:plc:...` or the email of the account.
+fn is_bluesky_identifier(identifier: &str) -> bool {
+    let is_handle = |handle: &str| {
+        let labels = handle.split('.').collect::<Vec<_>>();
+        labels.len() >= 2
+            && labels.iter().all(|label| {
+                (1..=63).contains(&label.len())
+                    && !label.starts_with('-')
+                    && !label.ends_with('-')
+                    && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+            })
+    };
+    if let Some(id) = identifier.strip_prefix("did:") {
+        return id.contains(':') && !id.contains(char::is_whitespace);
+    }
+    match identifier.split_once('@') {
+        Some((local, domain)) => {
+            !local.is_empty() && !local.contains(char::is_whitespace) && is_handle(domain)
+        }
+        None => is_handle(identifier),
+    }
+}
+
+impl Default for TrendingConfig {
+    fn default() -> Self {
+        Self {
+            languages: default_languages(),
+            since: default_since(),
+            min_stars: None,
+            min_stars_period: None,
+            repost_on_star_delta: None,
+            skip_empty_description: false,
+            allowed_description_langs: None,
+            max_posts_per_run: None,
+            max_per_author_per_run: None,
+            order: PostOrder::default(),
+        }
+    }
+}
+
+#[derive(Deserialize, Default)]
+struct GithubConfig {
+    /// Token for the GitHub API, which raises the rate limit.
+    token: Option<String>,
+    /// Search repos with the GitHub API instead of scraping the trending page.
+    #[serde(default)]
+    use_api: bool,
+    /// Host of the trending page and the repo links, e.g. of GitHub Enterprise. Defaults to
+    /// `https://github.com`.
+    host: Option<Url>,
+    /// Path of the trending page relative to `host`, with `{language}` and `{since}` replaced.
+    /// Defaults to `trending/{language}?since={since}`.
+    trending_path_template: Option<String>,
+    /// `Cookie` header sent to the trending page, for hosts which require signing in.
+    cookie: Option<String>,
+    /// Fetch the description of repos listed without one on the trending page from the API.
+    #[serde(default)]
+    backfill_descriptions: bool,
+}
+
+const DEFAULT_GITHUB_HOST: &str = "https://github.com";
+const DEFAULT_TRENDING_PATH_TEMPLATE: &str = "trending/{language}?since={since}";
+
+impl GithubConfig {
+    fn host(&self) -> Url {
+        self.host
+            .clone()
+            .unwrap_or_else(|| Url::parse(DEFAULT_GITHUB_HOST).unwrap())
+    }
+
+    fn trending_path_template(&self) -> &str {
+        self.trending_path_template
+            .as_deref()
+            .unwrap_or(DEFAULT_TRENDING_PATH_TEMPLATE)
+    }
+
+    /// Base of the REST API, which GitHub Enterprise serves under `/api/v3` of the host.
+    fn api_url(&self) -> Url {
+        let host = self.host();
+        if host.as_str() == Url::parse(DEFAULT_GITHUB_HOST).unwrap().as_str() {
+            Url::parse("https://api.github.com").unwrap()
+        } else {
+            repo_url(host.as_str(), &["api", "v3"])
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct RedisConfig {
+    #[serde(default)]
+    url: String,
+    /// Prepended to every key, for sharing a redis instance with others.
+    #[serde(default)]
+    key_prefix: String,
+}
+
+#[derive(Deserialize)]
+struct SqliteConfig {
+    path: PathBuf,
+}
+
+#[derive(Deserialize, Clone)]
+struct TwitterConfig {
+    #[serde(default)]
+    consumer_key: String,
+    #[serde(default)]
+    consumer_secret: String,
+    #[serde(default)]
+    token: String,
+    #[serde(default)]
+    secret: String,
+    #[serde(default)]
+    denylist: DenylistConfig,
+    /// Overrides `interval.post_interval` for this platform.
+    #[serde(default)]
+    post_interval: Option<u64>,
+    /// Overrides `interval.post_ttl` for this platform.
+    #[serde(default)]
+    post_ttl: Option<usize>,
+    /// Template of posts, see `DEFAULT_TEMPLATE`.
+    #[serde(default)]
+    template: Option<String>,
+    /// Show the stars in the default template. Custom templates can leave out `{stars}` instead.
+    #[serde(default = "default_true")]
+    show_stars: bool,
+    /// Optional parts appended to posts, as the `extras` table of the platform.
+    #[serde(default)]
+    extras: PostExtras,
+    /// Overrides the global hashtags.
+    #[serde(default)]
+    hashtags: Option<Vec<String>>,
+}
+
+#[derive(Deserialize, Clone)]
+struct MastodonConfig {
+    instance_url: Url,
+    #[serde(default)]
+    access_token: String,
+    /// Attach the social preview image of the repo instead of relying on the link card.
+    #[serde(default)]
+    attach_image: bool,
+    /// Posts are collapsed under this content warning.
+    #[serde(default)]
+    content_warning: Option<String>,
+    /// Post the repos of a loop as a thread, each replying to the previous one.
+    #[serde(default)]
+    thread: bool,
+    /// Posted first as the start of each thread, like `Today's trending Rust repos:`.
+    #[serde(default)]
+    thread_header: Option<String>,
+    /// Schedule statuses within a window of the day instead of posting them at once.
+    #[serde(default)]
+    schedule_spread: Option<ScheduleSpreadConfig>,
+    #[serde(default)]
+    denylist: DenylistConfig,
+    /// Overrides `interval.post_interval` for this platform.
+    #[serde(default)]
+    post_interval: Option<u64>,
+    /// Overrides `interval.post_ttl` for this platform.
+    #[serde(default)]
+    post_ttl: Option<usize>,
+    /// Template of posts, see `DEFAULT_TEMPLATE`.
+    #[serde(default)]
+    template: Option<String>,
+    /// Show the stars in the default template. Custom templates can leave out `{stars}` instead.
+    #[serde(default = "default_true")]
+    show_stars: bool,
+    /// Optional parts appended to posts, as the `extras` table of the platform.
+    #[serde(default)]
+    extras: PostExtras,
+    /// Overrides the global hashtags.
+    #[serde(default)]
+    hashtags: Option<Vec<String>>,
+}
+
+/// Window of the day in UTC, like `09:00` to `17:00`. Statuses are spread over it so that at most
+/// `max_per_day` are scheduled in a day, and only in the next window, so that they don't pile up in
+/// the following days.
+#[derive(Deserialize, Clone)]
+struct ScheduleSpreadConfig {
+    start: String,
+    end: String,
+    #[serde(default = "default_schedule_max_per_day")]
+    max_per_day: u32,
+}
+
+fn default_schedule_max_per_day() -> u32 {
+    8
+}
+
+impl ScheduleSpreadConfig {
+    fn start(&self) -> Result<Time> {
+        Ok(Time::parse(&self.start, TIME_OF_DAY_FORMAT)?)
+    }
+
+    fn end(&self) -> Result<Time> {
+        Ok(Time::parse(&self.end, TIME_OF_DAY_FORMAT)?)
+    }
+}
+
+#[derive(Deserialize, Clone)]
+struct BlueskyConfig {
+    host: String,
+    identifier: String,
+    #[serde(default)]
+    password: String,
+    /// File to persist the session in, so that restarts don't log in again.
+    #[serde(default)]
+    session_path: Option<PathBuf>,
+    /// Languages of posts as BCP 47 tags, so that Bluesky can filter them by language.
+    #[serde(default = "default_bluesky_langs")]
+    langs: Vec<String>,
+    /// Fetch the social preview image of the repo as the thumbnail of the link card.
+    #[serde(default = "default_true")]
+    embed_image: bool,
+    /// Create a threadgate with each post, so that nobody can reply to it.
+    #[serde(default)]
+    disable_replies: bool,
+    /// Self-labels of posts, one of `BLUESKY_SELF_LABELS`, to warn readers of their content.
+    #[serde(default)]
+    labels: Vec<String>,
+    #[serde(default)]
+    denylist: DenylistConfig,
+    /// Overrides `interval.post_interval` for this platform.
+    #[serde(default)]
+    post_interval: Option<u64>,
+    /// Overrides `interval.post_ttl` for this platform.
+    #[serde(default)]
+    post_ttl: Option<usize>,
+    /// Template of posts, see `DEFAULT_TEMPLATE`.
+    #[serde(default)]
+    template: Option<String>,
+    /// Show the stars in the default template. Custom templates can leave out `{stars}` instead.
+    #[serde(default = "default_true")]
+    show_stars: bool,
+    /// Optional parts appended to posts, as the `extras` table of the platform.
+    #[serde(default)]
+    extras: PostExtras,
+    /// Overrides the global hashtags.
+    #[serde(default)]
+    hashtags: Option<Vec<String>>,
+}
+
+#[derive(Deserialize, Clone)]
+struct DiscordConfig {
+    webhook_url: Url,
+    #[serde(default)]
+    denylist: DenylistConfig,
+    /// Overrides `interval.post_interval` for this platform.
+    #[serde(default)]
+    post_interval: Option<u64>,
+    /// Overrides `interval.post_ttl` for this platform.
+    #[serde(default)]
+    post_ttl: Option<usize>,
+}
+
+/// Posts as a bot user instead of a webhook, e.g. to react to or pin the posts later.
+#[derive(Deserialize, Clone)]
+struct DiscordBotConfig {
+    #[serde(default)]
+    token: String,
+    channel_id: u64,
+    #[serde(default)]
+    denylist: DenylistConfig,
+    /// Overrides `interval.post_interval` for this platform.
+    #[serde(default)]
+    post_interval: Option<u64>,
+    /// Overrides `interval.post_ttl` for this platform.
+    #[serde(default)]
+    post_ttl: Option<usize>,
+}
+
+#[derive(Deserialize, Clone)]
+struct TelegramConfig {
+    #[serde(default)]
+    bot_token: String,
+    chat_id: String,
+    #[serde(default)]
+    denylist: DenylistConfig,
+    /// Overrides `interval.post_interval` for this platform.
+    #[serde(default)]
+    post_interval: Option<u64>,
+    /// Overrides `interval.post_ttl` for this platform.
+    #[serde(default)]
+    post_ttl: Option<usize>,
+}
+
+#[derive(Deserialize, Clone)]
+struct WebhookConfig {
+    url: Url,
+    #[serde(default)]
+    headers: HashMap<String, String>,
+    #[serde(default)]
+    denylist: DenylistConfig,
+    /// Overrides `interval.post_interval` for this platform.
+    #[serde(default)]
+    post_interval: Option<u64>,
+    /// Overrides `interval.post_ttl` for this platform.
+    #[serde(default)]
+    post_ttl: Option<usize>,
+    /// Template of posts, see `DEFAULT_TEMPLATE`.
+    #[serde(default)]
+    template: Option<String>,
+    /// Show the stars in the default template. Custom templates can leave out `{stars}` instead.
+    #[serde(default = "default_true")]
+    show_stars: bool,
+    /// Optional parts appended to posts, as the `extras` table of the platform.
+    #[serde(default)]
+    extras: PostExtras,
+}
+
+#[derive(Deserialize, Clone)]
+struct SlackConfig {
+    webhook_url: Url,
+    #[serde(default)]
+    denylist: DenylistConfig,
+    /// Overrides `interval.post_interval` for this platform.
+    #[serde(default)]
+    post_interval: Option<u64>,
+    /// Overrides `interval.post_ttl` for this platform.
+    #[serde(default)]
+    post_ttl: Option<usize>,
+}
+
+#[derive(Deserialize, Clone)]
+struct MatrixConfig {
+    homeserver: Url,
+    #[serde(default)]
+    access_token: String,
+    /// Room ID like `!abcdef:matrix.org`, not an alias.
+    room_id: String,
+    #[serde(default)]
+    denylist: DenylistConfig,
+    /// Overrides `interval.post_interval` for this platform.
+    #[serde(default)]
+    post_interval: Option<u64>,
+    /// Overrides `interval.post_ttl` for this platform.
+    #[serde(default)]
+    post_ttl: Option<usize>,
+}
+
+#[derive(Deserialize, Clone)]
+struct LemmyConfig {
+    instance_url: Url,
+    username: String,
+    #[serde(default)]
+    password: String,
+    /// Name of the community to post to, like `rust`.
+    community: String,
+    #[serde(default)]
+    denylist: DenylistConfig,
+    /// Overrides `interval.post_interval` for this platform.
+    #[serde(default)]
+    post_interval: Option<u64>,
+    /// Overrides `interval.post_ttl` for this platform.
+    #[serde(default)]
+    post_ttl: Option<usize>,
+}
+
+#[derive(Deserialize, Clone)]
+struct RedditConfig {
+    /// ID and secret of a "script" app, created at https://www.reddit.com/prefs/apps.
+    client_id: String,
+    #[serde(default)]
+    client_secret: String,
+    /// Account of the app, which submits the posts.
+    username: String,
+    #[serde(default)]
+    password: String,
+    /// Name of the subreddit without `r/`, like `rust`.
+    subreddit: String,
+    #[serde(default)]
+    denylist: DenylistConfig,
+    /// Overrides `interval.post_interval` for this platform.
+    #[serde(default)]
+    post_interval: Option<u64>,
+    /// Overrides `interval.post_ttl` for this platform.
+    #[serde(default)]
+    post_ttl: Option<usize>,
+}
+
+#[derive(Deserialize, Clone)]
+struct NostrConfig {
+    /// Secret key in hex.
+    #[serde(default)]
+    secret_key: String,
+    relays: Vec<Url>,
+    #[serde(default)]
+    denylist: DenylistConfig,
+    /// Overrides `interval.post_interval` for this platform.
+    #[serde(default)]
+    post_interval: Option<u64>,
+    /// Overrides `interval.post_ttl` for this platform.
+    #[serde(default)]
+    post_ttl: Option<usize>,
+    /// Template of posts, see `DEFAULT_TEMPLATE`.
+    #[serde(default)]
+    template: Option<String>,
+    /// Show the stars in the default template. Custom templates can leave out `{stars}` instead.
+    #[serde(default = "default_true")]
+    show_stars: bool,
+    /// Optional parts appended to posts, as the `extras` table of the platform.
+    #[serde(default)]
+    extras: PostExtras,
+    /// Overrides the global hashtags.
+    #[serde(default)]
+    hashtags: Option<Vec<String>>,
+}
+
+/// A single post listing the top repos of the day, posted to Twitter, Mastodon, Telegram and
+/// Nostr.
+#[derive(Deserialize)]
+struct DigestConfig {
+    #[serde(default = "default_true")]
+    enabled: bool,
+    /// Number of repos listed, fewer on platforms where they don't fit the length limit.
+    #[serde(default = "default_digest_top_n")]
+    top_n: usize,
+    /// Time of day in UTC like `18:00`, posted in the first loop after it.
+    time: String,
+    /// Post only the digest, instead of a post per repo as well.
+    #[serde(default)]
+    only: bool,
+}
+
+fn default_digest_top_n() -> usize {
+    5
+}
+
+impl DigestConfig {
+    fn time(&self) -> Result<Time> {
+        Ok(Time::parse(&self.time, TIME_OF_DAY_FORMAT)?)
+    }
+}
+
+#[derive(Deserialize)]
+struct FeedConfig {
+    output_path: PathBuf,
+    max_items: usize,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+struct DenylistConfig {
+    names: Vec<String>,
+    authors: Vec<String>,
+    descriptions: Vec<String>,
+    /// Regexes searched in names, e.g. `^awesome-`. Invalid regexes fail loading the config.
+    #[serde(deserialize_with = "deserialize_regexes")]
+    name_patterns: Vec<Regex>,
+    #[serde(deserialize_with = "deserialize_regexes")]
+    author_patterns: Vec<Regex>,
+    #[serde(deserialize_with = "deserialize_regexes")]
+    description_patterns: Vec<Regex>,
+    /// Skips repos created fewer days ago. Only repos fetched with the GitHub API have a
+    /// creation date.
+    min_age_days: Option<i64>,
+    max_age_days: Option<i64>,
+    /// GitHub topics, compared case-insensitively. Only filled with the GitHub API.
+    denied_topics: Vec<String>,
+}
+
+impl DenylistConfig {
+    /// Number of entries, counting each pattern, topic and age limit as one.
+    fn len(&self) -> usize {
+        self.names.len()
+            + self.authors.len()
+            + self.descriptions.len()
+            + self.name_patterns.len()
+            + self.author_patterns.len()
+            + self.description_patterns.len()
+            + self.denied_topics.len()
+            + usize::from(self.min_age_days.is_some())
+            + usize::from(self.max_age_days.is_some())
+    }
+
+    fn contains(&self, repo: &Repo) -> bool {
+        self.contains_at(repo, OffsetDateTime::now_utc())
+    }
+
+    fn contains_at(&self, repo: &Repo, now: OffsetDateTime) -> bool {
+        let age = repo.created_at.map(|created_at| now - created_at);
+        self.names.contains(&repo.name)
+            || self.authors.contains(&repo.author)
+            || self.descriptions.iter().any(|description| {
+                repo.description
+                    .to_lowercase()
+                    .contains(&description.to_lowercase())
+            })
+            || self.name_patterns.iter().any(|re| re.is_match(&repo.name))
+            || self
+                .author_patterns
+                .iter()
+                .any(|re| re.is_match(&repo.author))
+            || self
+                .description_patterns
+                .iter()
+                .any(|re| re.is_match(&repo.description))
+            || matches!((age, self.min_age_days), (Some(age), Some(days)) if age < time::Duration::days(days))
+            || matches!((age, self.max_age_days), (Some(age), Some(days)) if age > time::Duration::days(days))
+            || repo.topics.iter().any(|topic| {
+                self.denied_topics
+                    .iter()
+                    .any(|denied| denied.eq_ignore_ascii_case(topic))
+            })
+    }
+}
+
+fn deserialize_regexes<'de, D>(deserializer: D) -> Result<Vec<Regex>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|pattern| Regex::new(pattern).map_err(serde::de::Error::custom))
+        .collect()
+}
+
+/// Accepts either a single table or an array of tables. Unlike an untagged enum, the tables are
+/// not buffered, so that unknown keys in them are still reported by `read_config`.
+fn deserialize_one_or_many<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    struct OneOrManyVisitor<T>(std::marker::PhantomData<T>);
+
+    impl<'de, T: Deserialize<'de>> serde::de::Visitor<'de> for OneOrManyVisitor<T> {
+        type Value = Vec<T>;
+
+        fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+            formatter.write_str("a table or an array of tables")
+        }
+
+        fn visit_map<A: serde::de::MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+            T::deserialize(serde::de::value::MapAccessDeserializer::new(map))
+                .map(|value| vec![value])
+        }
+
+        fn visit_seq<A: serde::de::SeqAccess<'de>>(self, seq: A) -> Result<Self::Value, A::Error> {
+            Vec::deserialize(serde::de::value::SeqAccessDeserializer::new(seq))
+        }
+    }
+
+    deserializer.deserialize_any(OneOrManyVisitor(std::marker::PhantomData))
+}
+
+#[derive(Deserialize)]
+struct Config {
+    /// Address to serve Prometheus metrics on. Metrics are not served when unset.
+    #[serde(default)]
+    metrics_bind: Option<SocketAddr>,
+    /// Address to serve `/healthz` on. The healthcheck is not served when unset.
+    #[serde(default)]
+    health_bind: Option<SocketAddr>,
+    /// URL to POST `{"level": "error", "message": "..."}` to when a loop fails or a platform keeps
+    /// failing, see `Alerts`.
+    #[serde(default)]
+    alert_webhook: Option<Url>,
+    interval: IntervalConfig,
+    #[serde(default)]
+    trending: TrendingConfig,
+    #[serde(default)]
+    github: GithubConfig,
+    #[serde(default)]
+    redis: Option<RedisConfig>,
+    /// Store posted repos in SQLite instead of redis.
+    #[serde(default)]
+    sqlite: Option<SqliteConfig>,
+    #[serde(default)]
+    twitter: Option<TwitterConfig>,
+    #[serde(default, deserialize_with = "deserialize_one_or_many")]
+    mastodon: Vec<MastodonConfig>,
+    #[serde(default)]
+    bluesky: Option<BlueskyConfig>,
+    #[serde(default)]
+    discord: Option<DiscordConfig>,
+    #[serde(default)]
+    discord_bot: Option<DiscordBotConfig>,
+    #[serde(default)]
+    telegram: Option<TelegramConfig>,
+    #[serde(default)]
+    webhook: Option<WebhookConfig>,
+    #[serde(default)]
+    slack: Option<SlackConfig>,
+    #[serde(default)]
+    matrix: Option<MatrixConfig>,
+    #[serde(default)]
+    nostr: Option<NostrConfig>,
+    #[serde(default)]
+    lemmy: Option<LemmyConfig>,
+    #[serde(default)]
+    reddit: Option<RedditConfig>,
+    #[serde(default)]
+    feed: Option<FeedConfig>,
+    #[serde(default)]
+    digest: Option<DigestConfig>,
+    /// Log posts instead of sending them, and don't mark repos as posted.
+    #[serde(default)]
+    dry_run: bool,
+    /// Hashtags appended to posts on Twitter, Mastodon and Bluesky.
+    #[serde(default)]
+    hashtags: Vec<String>,
+    #[serde(default)]
+    format: FormatConfig,
+    #[serde(default)]
+    log_format: LogFormat,
+    /// Timeout of outbound HTTP requests in seconds.
+    #[serde(default = "default_http_timeout_secs")]
+    http_timeout_secs: u64,
+    /// Proxy of outbound HTTP requests, like `http://proxy:3128` or `socks5://proxy:1080`. Nostr
+    /// relays are connected directly, so it is rejected together with `nostr`.
+    #[serde(default)]
+    proxy: Option<Url>,
+    /// Key in the store which pauses fetching and posting while set to a truthy value.
+    #[serde(default = "default_paused_key")]
+    paused_key: String,
+    denylist: DenylistConfig,
+}
+
+/// Options of an enabled platform, see `Config::platforms`.
+struct Platform<'a> {
+    name: String,
+    denylist: &'a DenylistConfig,
+    post_ttl: Option<usize>,
+}
+
+/// A trending repo, from the trending page or the GitHub API.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct Repo {
+    pub author: String,
+    pub description: String,
+    pub name: String,
+    pub stars: usize,
+    /// Stars gained during the trending range, e.g. "239 stars today".
+    #[serde(default)]
+    pub stars_period: usize,
+    /// Trending range of `stars_period`.
+    #[serde(skip)]
+    pub period: Option<Since>,
+    #[serde(default)]
+    pub forks: usize,
+    /// Primary language of the repo, e.g. "Rust".
+    #[serde(default)]
+    pub language: Option<String>,
+    /// Not shown on the trending page, so only known when fetched with the GitHub API.
+    #[serde(skip)]
+    pub created_at: Option<OffsetDateTime>,
+    #[serde(default)]
+    pub topics: Vec<String>,
+    /// Handles listed as "Built by" on the trending page. Empty with the GitHub API.
+    #[serde(default)]
+    pub contributors: Vec<String>,
+}
+
+#[inline]
+fn now_ts() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+/// Reads the config, along with the keys that don't map to any field, e.g. misspelled ones.
+fn read_config(path: &Path) -> Result<(Config, Vec<String>)> {
+    let mut file = File::open(path)?;
+    let mut content = String::new();
+    file.read_to_string(&mut content)?;
+    let (mut config, unknown_keys) = parse_config(&content)?;
+    config.apply_env_overrides(|name| std::env::var(name).ok())?;
+    Ok((config, unknown_keys))
+}
+
+fn parse_config(content: &str) -> Result<(Config, Vec<String>)> {
+    let mut unknown_keys = Vec::new();
+    let config = serde_ignored::deserialize(&mut toml::Deserializer::new(content), |path| {
+        unknown_keys.push(config_key_path(&path))
+    })?;
+    Ok((config, unknown_keys))
+}
+
+/// Dotted path like `mastodon.0.template`, leaving out the `?` of optional tables that
+/// `serde_ignored` shows.
+fn config_key_path(path: &serde_ignored::Path) -> String {
+    let (parent, key) = match path {
+        serde_ignored::Path::Root => return String::new(),
+        serde_ignored::Path::Seq { parent, index } => (parent, index.to_string()),
+        serde_ignored::Path::Map { parent, key } => (parent, key.clone()),
+        serde_ignored::Path::Some { parent }
+        | serde_ignored::Path::NewtypeStruct { parent }
+        | serde_ignored::Path::NewtypeVariant { parent } => return config_key_path(parent),
+    };
+    match config_key_path(parent) {
+        parent if parent.is_empty() => key,
+        parent => format!("{}.{}", parent, key),
+    }
+}
+
+/// Replaces the password of the URL, e.g. `redis://:password@localhost`.
+fn redact_url(url: &str) -> String {
+    match Url::parse(url) {
+        Ok(mut url) if url.password().is_some() => {
+            let _ = url.set_password(Some("redacted"));
+            url.to_string()
+        }
+        Ok(url) => url.to_string(),
+        Err(_) => "<invalid URL>".to_string(),
+    }
+}
+
+impl Config {
+    /// Seconds to wait between posted repos. Platforms are posted concurrently, so the slowest
+    /// platform decides the interval.
+    fn post_interval(&self) -> u64 {
+        let global = self.interval.post_interval;
+        let intervals = vec![
+            self.twitter.as_ref().map(|config| config.post_interval),
+            self.bluesky.as_ref().map(|config| config.post_interval),
+            self.discord.as_ref().map(|config| config.post_interval),
+            self.discord_bot.as_ref().map(|config| config.post_interval),
+            self.telegram.as_ref().map(|config| config.post_interval),
+            self.webhook.as_ref().map(|config| config.post_interval),
+            self.slack.as_ref().map(|config| config.post_interval),
+            self.matrix.as_ref().map(|config| config.post_interval),
+            self.nostr.as_ref().map(|config| config.post_interval),
+            self.lemmy.as_ref().map(|config| config.post_interval),
+            self.reddit.as_ref().map(|config| config.post_interval),
+        ]
+        .into_iter()
+        .flatten()
+        .chain(self.mastodon.iter().map(|config| config.post_interval))
+        .map(|interval| interval.unwrap_or(global));
+        intervals.max().unwrap_or(global)
+    }
+
+    fn http_config(&self) -> Result<HttpConfig> {
+        Ok(HttpConfig {
+            timeout: Duration::from_secs(self.http_timeout_secs),
+            proxy: self
+                .proxy
+                .clone()
+                .map(reqwest::Proxy::all)
+                .transpose()
+                .context("While setting proxy")?,
+        })
+    }
+
+    /// Names of the enabled platforms, as used in their keys.
+    fn platform_names(&self) -> Vec<String> {
+        self.platforms()
+            .into_iter()
+            .map(|platform| platform.name)
+            .collect()
+    }
+
+    /// Enabled platforms with the options shared by all of them, named as in their keys.
+    fn platforms(&self) -> Vec<Platform<'_>> {
+        let mut platforms = Vec::new();
+        if let Some(config) = &self.twitter {
+            platforms.push(Platform {
+                name: "twitter".to_string(),
+                denylist: &config.denylist,
+                post_ttl: config.post_ttl,
+            });
+        }
+        platforms.extend(self.mastodon.iter().map(|config| Platform {
+            name: mastodon_platform_name(config),
+            denylist: &config.denylist,
+            post_ttl: config.post_ttl,
+        }));
+        for (name, options) in [
+            (
+                "bluesky",
+                self.bluesky
+                    .as_ref()
+                    .map(|config| (&config.denylist, config.post_ttl)),
+            ),
+            (
+                "discord",
+                self.discord
+                    .as_ref()
+                    .map(|config| (&config.denylist, config.post_ttl)),
+            ),
+            (
+                "discord_bot",
+                self.discord_bot
+                    .as_ref()
+                    .map(|config| (&config.denylist, config.post_ttl)),
+            ),
+            (
+                "telegram",
+                self.telegram
+                    .as_ref()
+                    .map(|config| (&config.denylist, config.post_ttl)),
+            ),
+            (
+                "webhook",
+                self.webhook
+                    .as_ref()
+                    .map(|config| (&config.denylist, config.post_ttl)),
+            ),
+            (
+                "slack",
+                self.slack
+                    .as_ref()
+                    .map(|config| (&config.denylist, config.post_ttl)),
+            ),
+            (
+                "matrix",
+                self.matrix
+                    .as_ref()
+                    .map(|config| (&config.denylist, config.post_ttl)),
+            ),
+            (
+                "nostr",
+                self.nostr
+                    .as_ref()
+                    .map(|config| (&config.denylist, config.post_ttl)),
+            ),
+            (
+                "lemmy",
+                self.lemmy
+                    .as_ref()
+                    .map(|config| (&config.denylist, config.post_ttl)),
+            ),
+            (
+                "reddit",
+                self.reddit
+                    .as_ref()
+                    .map(|config| (&config.denylist, config.post_ttl)),
+            ),
+        ] {
+            if let Some((denylist, post_ttl)) = options {
+                platforms.push(Platform {
+                    name: name.to_string(),
+                    denylist,
+                    post_ttl,
+                });
+            }
+        }
+        platforms
+    }
+
+    /// Seconds a repo posted to the platform is remembered.
+    fn post_ttl(&self, platform: &str) -> usize {
+        self.platforms()
+            .into_iter()
+            .find(|candidate| candidate.name == platform)
+            .and_then(|platform| platform.post_ttl)
+            .unwrap_or(self.interval.post_ttl)
+    }
+
+    /// Shortest `post_ttl` of the enabled platforms, used for the key of the repo so that it's
+    /// checked again once any platform forgets it.
+    fn min_post_ttl(&self) -> usize {
+        self.platforms()
+            .into_iter()
+            .map(|platform| platform.post_ttl.unwrap_or(self.interval.post_ttl))
+            .min()
+            .unwrap_or(self.interval.post_ttl)
+    }
+
+    /// Summary of the effective config for `--check-config`, leaving out secrets.
+    fn describe(&self) -> String {
+        let mut lines = Vec::new();
+        lines.push(format!(
+            "store: {}",
+            match (&self.redis, &self.sqlite) {
+                (Some(redis), _) => format!("redis {}", redact_url(&redis.url)),
+                (None, Some(sqlite)) => format!("SQLite {}", sqlite.path.display()),
+                (None, None) => "none".to_string(),
+            }
+        ));
+        lines.push(format!(
+            "trending: {} ({}) from the {}",
+            self.trending.languages.join(", "),
+            self.trending
+                .since
+                .iter()
+                .map(|since| since.as_str())
+                .collect::<Vec<_>>()
+                .join(", "),
+            if self.github.use_api {
+                "GitHub API"
+            } else {
+                "trending page"
+            }
+        ));
+        lines.push(format!("github host: {}", self.github.host()));
+        lines.push(format!(
+            "fetch interval: {}s, plus up to {}s",
+            self.interval.fetch_interval, self.interval.fetch_jitter_secs
+        ));
+        lines.push(format!(
+            "post interval: {}s, plus up to {}s",
+            self.post_interval(),
+            self.interval.post_jitter_secs
+        ));
+        lines.push(format!("post ttl: {}s", self.interval.post_ttl));
+        lines.push(format!("denylist: {} entries", self.denylist.len()));
+        let platforms = self.platforms();
+        if platforms.is_empty() {
+            lines.push("platforms: none".to_string());
+        }
+        for platform in platforms {
+            lines.push(format!(
+                "platform {}: {} denylist entries, post ttl {}s",
+                platform.name,
+                platform.denylist.len(),
+                platform.post_ttl.unwrap_or(self.interval.post_ttl)
+            ));
+        }
+        if let Some(feed) = &self.feed {
+            lines.push(format!("feed: {}", feed.output_path.display()));
+        }
+        if let Some(digest) = self.digest.as_ref().filter(|digest| digest.enabled) {
+            lines.push(format!(
+                "digest: top {} at {} UTC{}",
+                digest.top_n,
+                digest.time,
+                if digest.only {
+                    ", instead of each repo"
+                } else {
+                    ""
+                }
+            ));
+        }
+        if let Some(proxy) = &self.proxy {
+            lines.push(format!("proxy: {}", redact_url(proxy.as_str())));
+        }
+        if self.dry_run {
+            lines.push("dry run: posts are logged instead of sent".to_string());
+        }
+        lines.join("\n")
+    }
+
+    /// Overrides secrets with environment variables like `RT_MASTODON_ACCESS_TOKEN`, so that they
+    /// don't have to be written in the config file. Mastodon instances after the first one are
+    /// overridden with `RT_MASTODON_<index>_ACCESS_TOKEN`, where the first one is `0`.
+    fn apply_env_overrides(&mut self, env: impl Fn(&str) -> Option<String>) -> Result<()> {
+        let set = |field: &mut String, name: &str| {
+            if let Some(value) = env(name) {
+                *field = value;
+            }
+        };
+
+        if let Some(token) = env("RT_GITHUB_TOKEN") {
+            self.github.token = Some(token);
+        }
+        if let Some(cookie) = env("RT_GITHUB_COOKIE") {
+            self.github.cookie = Some(cookie);
+        }
+        if let Some(url) = env("RT_REDIS_URL") {
+            match &mut self.redis {
+                Some(config) => config.url = url,
+                None if self.sqlite.is_none() => {
+                    self.redis = Some(RedisConfig {
+                        url,
+                        key_prefix: String::new(),
+                    })
+                }
+                None => {}
+            }
+        }
+        if let Some(config) = &mut self.twitter {
+            set(&mut config.consumer_key, "RT_TWITTER_CONSUMER_KEY");
+            set(&mut config.consumer_secret, "RT_TWITTER_CONSUMER_SECRET");
+            set(&mut config.token, "RT_TWITTER_TOKEN");
+            set(&mut config.secret, "RT_TWITTER_SECRET");
+        }
+        for (index, config) in self.mastodon.iter_mut().enumerate() {
+            if index == 0 {
+                set(&mut config.access_token, "RT_MASTODON_ACCESS_TOKEN");
+            }
+            set(
+                &mut config.access_token,
+                &format!("RT_MASTODON_{}_ACCESS_TOKEN", index),
+            );
+        }
+        if let Some(config) = &mut self.bluesky {
+            set(&mut config.password, "RT_BLUESKY_PASSWORD");
+        }
+        if let Some(config) = &mut self.discord_bot {
+            set(&mut config.token, "RT_DISCORD_BOT_TOKEN");
+        }
+        if let Some(config) = &mut self.telegram {
+            set(&mut config.bot_token, "RT_TELEGRAM_BOT_TOKEN");
+        }
+        if let Some(config) = &mut self.matrix {
+            set(&mut config.access_token, "RT_MATRIX_ACCESS_TOKEN");
+        }
+        if let Some(config) = &mut self.nostr {
+            set(&mut config.secret_key, "RT_NOSTR_SECRET_KEY");
+        }
+        if let Some(config) = &mut self.lemmy {
+            set(&mut config.password, "RT_LEMMY_PASSWORD");
+        }
+        if let Some(config) = &mut self.reddit {
+            set(&mut config.client_secret, "RT_REDDIT_CLIENT_SECRET");
+            set(&mut config.password, "RT_REDDIT_PASSWORD");
+        }
+        if let Some(format) = env("RT_LOG_FORMAT") {
+            self.log_format = format.parse().context("While reading RT_LOG_FORMAT")?;
+        }
+        Ok(())
+    }
+
+    /// Checks the whole config, so that mistakes are found at startup instead of in the middle of
+    /// a loop. Every problem found is reported at once.
+    fn validate(&self) -> Result<()> {
+        let mut problems = Vec::new();
+
+        if self.interval.post_ttl == 0 {
+            problems.push("interval.post_ttl must not be zero".to_string());
+        }
+        for platform in self.platforms() {
+            if platform.post_ttl == Some(0) {
+                problems.push(format!("post_ttl of {} must not be zero", platform.name));
+            }
+        }
+        if self.interval.fetch_interval == 0 {
+            problems.push("interval.fetch_interval must not be zero".to_string());
+        }
+        if self.interval.post_interval == 0 {
+            problems.push("interval.post_interval must not be zero".to_string());
+        }
+        if self.http_timeout_secs == 0 {
+            problems.push("http_timeout_secs must not be zero".to_string());
+        }
+        if let Some(url) = &self.alert_webhook {
+            if !matches!(url.scheme(), "http" | "https") || url.host().is_none() {
+                problems.push(format!(
+                    "alert_webhook must be an HTTP URL with a host, but is {}",
+                    url
+                ));
+            }
+        }
+        if let Some(proxy) = &self.proxy {
+            if !matches!(proxy.scheme(), "http" | "https" | "socks5" | "socks5h")
+                || proxy.host().is_none()
+            {
+                problems.push(format!(
+                    "proxy must be an HTTP or SOCKS5 URL with a host, but is {}",
+                    proxy
+                ));
+            }
+            // Rejected instead of bypassing the proxy, which may be the only way out
+            if self.nostr.is_some() {
+                problems.push(
+                    "proxy can't be used with nostr, whose relays are connected directly"
+                        .to_string(),
+                );
+            }
+        }
+        let host = self.github.host();
+        if !matches!(host.scheme(), "http" | "https") || host.host().is_none() {
+            problems.push(format!(
+                "github.host must be an HTTP URL with a host, but is {}",
+                host
+            ));
+        } else if let Err(error) = trending_url(
+            &host,
+            self.github.trending_path_template(),
+            "rust",
+            Since::Daily,
+        ) {
+            problems.push(format!(
+                "github.trending_path_template is not a valid path: {}",
+                error
+            ));
+        }
+        if self.trending.languages.is_empty() {
+            problems.push("trending.languages must contain at least one language".to_string());
+        }
+        if self.trending.since.is_empty() {
+            problems.push("trending.since must contain at least one range".to_string());
+        }
+        if self.trending.max_posts_per_run == Some(0) {
+            problems.push("trending.max_posts_per_run must not be zero".to_string());
+        }
+        for code in self.trending.allowed_description_langs.iter().flatten() {
+            if whatlang::Lang::from_code(code.to_lowercase()).is_none() {
+                problems.push(format!(
+                    "trending.allowed_description_langs must be ISO 639-3 codes like eng, but \
+                     contains {:?}",
+                    code
+                ));
+            }
+        }
+        if self.trending.max_per_author_per_run == Some(0) {
+            problems.push("trending.max_per_author_per_run must not be zero".to_string());
+        }
+        if let Some(config) = &self.digest {
+            if config.top_n == 0 {
+                problems.push("digest.top_n must not be zero".to_string());
+            }
+            if config.time().is_err() {
+                problems.push(format!(
+                    "digest.time must be a time of day like 18:00, but is {:?}",
+                    config.time
+                ));
+            }
+            if self.twitter.is_none()
+                && self.mastodon.is_empty()
+                && self.telegram.is_none()
+                && self.nostr.is_none()
+            {
+                problems.push(
+                    "digest needs one of twitter, mastodon, telegram or nostr to be configured"
+                        .to_string(),
+                );
+            }
+        }
+        if self
+            .redis
+            .as_ref()
+            .is_some_and(|config| config.url.is_empty())
+        {
+            problems.push("redis.url is empty".to_string());
+        }
+        match (&self.redis, &self.sqlite) {
+            (None, None) => problems.push("Either redis or sqlite must be configured".to_string()),
+            (Some(_), Some(_)) => {
+                problems.push("Only one of redis and sqlite can be configured".to_string())
+            }
+            _ => {}
+        }
+
+        if self.twitter.is_none()
+            && self.mastodon.is_empty()
+            && self.bluesky.is_none()
+            && self.discord.is_none()
+            && self.discord_bot.is_none()
+            && self.telegram.is_none()
+            && self.webhook.is_none()
+            && self.slack.is_none()
+            && self.matrix.is_none()
+            && self.nostr.is_none()
+            && self.lemmy.is_none()
+            && self.reddit.is_none()
+            && self.feed.is_none()
+        {
+            // Otherwise repos would be marked posted, and skipped until post_ttl, without being
+            // posted anywhere
+            problems.push("At least one platform or feed must be configured".to_string());
+        }
+        for config in &self.mastodon {
+            let url = &config.instance_url;
+            if !matches!(url.scheme(), "http" | "https") || url.host().is_none() {
+                problems.push(format!(
+                    "mastodon.instance_url must be an HTTP URL with a host, but is {}",
+                    url
+                ));
+            }
+            if config.access_token.is_empty() {
+                problems.push(format!("mastodon.access_token for {} is empty", url));
+            }
+            if let Some(spread) = &config.schedule_spread {
+                match (spread.start(), spread.end()) {
+                    (Ok(start), Ok(end)) if start < end => {
+                        if spread.max_per_day == 0 {
+                            problems.push(format!(
+                                "mastodon.schedule_spread.max_per_day for {} must be positive",
+                                url
+                            ));
+                        }
+                    }
+                    (Ok(_), Ok(_)) => problems.push(format!(
+                        "mastodon.schedule_spread.start for {} must be before its end",
+                        url
+                    )),
+                    _ => problems.push(format!(
+                        "mastodon.schedule_spread for {} must be times of day like 09:00, \
+                        but is {:?} to {:?}",
+                        url, spread.start, spread.end
+                    )),
+                }
+                // Scheduled statuses have no ID of a status to reply to until they are posted
+                if config.thread {
+                    problems.push(format!(
+                        "mastodon.thread for {} can't be used with schedule_spread",
+                        url
+                    ));
+                }
+            }
+        }
+        if let Some(config) = &self.bluesky {
+            if let Err(error) = Url::parse(&config.host) {
+                problems.push(format!(
+                    "bluesky.host must be a URL, but is {:?}: {}",
+                    config.host, error
+                ));
+            }
+            if config.identifier.is_empty() {
+                problems.push("bluesky.identifier is empty".to_string());
+            } else if !is_bluesky_identifier(&config.identifier) {
+                problems.push(format!(
+                    "bluesky.identifier must be a handle like alice.bsky.social without the \
+                     leading @, a DID or an email, but is {:?}",
+                    config.identifier
+                ));
+            }
+            if config.password.is_empty() {
+                problems.push("bluesky.password is empty".to_string());
+            }
+            for lang in &config.langs {
+                if !is_language_tag(lang) {
+                    problems.push(format!(
+                        "bluesky.langs must be BCP 47 language tags, but contains {:?}",
+                        lang
+                    ));
+                }
+            }
+            for label in &config.labels {
+                if !BLUESKY_SELF_LABELS.contains(&label.as_str()) {
+                    problems.push(format!(
+                        "bluesky.labels must be one of {}, but contains {:?}",
+                        BLUESKY_SELF_LABELS.join(", "),
+                        label
+                    ));
+                }
+            }
+        }
+        if let Some(config) = &self.twitter {
+            if [
+                &config.consumer_key,
+                &config.consumer_secret,
+                &config.token,
+                &config.secret,
+            ]
+            .iter()
+            .any(|secret| secret.is_empty())
+            {
+                problems.push("twitter credentials must not be empty".to_string());
+            }
+        }
+        if let Some(config) = &self.discord_bot {
+            if config.token.is_empty() {
+                problems.push("discord_bot.token is empty".to_string());
+            }
+            if config.channel_id == 0 {
+                problems.push("discord_bot.channel_id must not be 0".to_string());
+            }
+        }
+        if self
+            .telegram
+            .as_ref()
+            .is_some_and(|config| config.bot_token.is_empty())
+        {
+            problems.push("telegram.bot_token is empty".to_string());
+        }
+        if let Some(config) = &self.matrix {
+            if config.access_token.is_empty() {
+                problems.push("matrix.access_token is empty".to_string());
+            }
+            if !config.room_id.starts_with('!') {
+                problems.push(format!(
+                    "matrix.room_id must be a room ID starting with '!', but is {:?}",
+                    config.room_id
+                ));
+            }
+        }
+        if let Some(config) = &self.nostr {
+            if let Err(error) = KeyPair::from_seckey_str(SECP256K1, &config.secret_key) {
+                problems.push(format!("nostr.secret_key is not a valid key: {}", error));
+            }
+            if config.relays.is_empty() {
+                problems.push("nostr.relays must contain at least one relay".to_string());
+            }
+        }
+        if let Some(config) = &self.lemmy {
+            let url = &config.instance_url;
+            if !matches!(url.scheme(), "http" | "https") || url.host().is_none() {
+                problems.push(format!(
+                    "lemmy.instance_url must be an HTTP URL with a host, but is {}",
+                    url
+                ));
+            }
+            if config.username.is_empty() || config.password.is_empty() {
+                problems.push("lemmy credentials must not be empty".to_string());
+            }
+            if config.community.is_empty() {
+                problems.push("lemmy.community is empty".to_string());
+            }
+        }
+        if let Some(config) = &self.reddit {
+            if config.client_id.is_empty()
+                || config.client_secret.is_empty()
+                || config.username.is_empty()
+                || config.password.is_empty()
+            {
+                problems.push("reddit credentials must not be empty".to_string());
+            }
+            if config.subreddit.is_empty() || config.subreddit.contains('/') {
+                problems.push(format!(
+                    "reddit.subreddit must be a name without r/, but is {:?}",
+                    config.subreddit
+                ));
+            }
+        }
+
+        if !problems.is_empty() {
+            bail!("Invalid config:\n- {}", problems.join("\n- "));
+        }
+        Ok(())
+    }
+}
+
+static REPO_SELECTOR: Lazy<Selector> =
+    Lazy::new(|| Selector::parse(".Box article.Box-row").unwrap());
+static TITLE_SELECTOR: Lazy<Selector> = Lazy::new(|| Selector::parse(".h3").unwrap());
+static DESCRIPTION_SELECTOR: Lazy<Selector> = Lazy::new(|| Selector::parse("p.my-1").unwrap());
+static STARS_SELECTOR: Lazy<Selector> =
+    Lazy::new(|| Selector::parse(".mr-3 svg[aria-label='star']").unwrap());
+static FORKS_SELECTOR: Lazy<Selector> =
+    Lazy::new(|| Selector::parse(".mr-3 svg[aria-label='fork']").unwrap());
+static STARS_PERIOD_SELECTOR: Lazy<Selector> =
+    Lazy::new(|| Selector::parse(".float-sm-right").unwrap());
+static LANGUAGE_SELECTOR: Lazy<Selector> =
+    Lazy::new(|| Selector::parse("[itemprop=programmingLanguage]").unwrap());
+static CONTRIBUTOR_SELECTOR: Lazy<Selector> =
+    Lazy::new(|| Selector::parse("a[data-hovercard-type='user']").unwrap());
+
+/// Parses counts as rendered by GitHub, like "1,234", "12.3k" or "1m".
+fn parse_count(text: &str) -> Option<usize> {
+    let text = text.trim().replace(',', "");
+    let (number, multiplier) = match text.char_indices().last()? {
+        (i, 'k') | (i, 'K') => (&text[..i], 1_000.0),
+        (i, 'm') | (i, 'M') => (&text[..i], 1_000_000.0),
+        _ => return text.parse().ok(),
+    };
+    let number: f64 = number.parse().ok()?;
+    if !number.is_finite() || number < 0.0 {
+        return None;
+    }
+    Some((number * multiplier).round() as usize)
+}
+
+/// Failure to fetch trending repos, telling whether fetching again may succeed.
+#[derive(Debug, thiserror::Error)]
+pub enum FetchError {
+    #[error("Invalid URL of the trending page: {0}")]
+    Url(#[from] url::ParseError),
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+    #[error("No repos found in the trending page, its layout may have changed or the request may have been blocked")]
+    NoRepos,
+}
+
+impl FetchError {
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, FetchError::Http(error) if is_retryable(error))
+    }
+}
+
+/// Parses the repos listed on the trending page, failing if there are none.
+pub fn parse_trending(html: String) -> Result<Vec<Repo>, FetchError> {
+    // Reference: https://github.com/huchenme/github-trending-api/blob/cf898c27850be407fb3f8dd31a4d1c3256ec6e12/src/functions/utils/fetch.js#L30-L103
+
+    let html = scraper::Html::parse_document(&html);
+    let repos = html
+        .select(&REPO_SELECTOR)
+        .filter_map(|repo| {
+            let title = repo
+                .select(&TITLE_SELECTOR)
+                .next()?
+                .text()
+                .fold(String::new(), |acc, s| acc + s);
+            let (author, name) = title.split_once('/')?;
+            let author = sanitize_repo_segment(author);
+            let name = sanitize_repo_segment(name);
+            if author.is_empty() || name.is_empty() {
+                return None;
+            }
+
+            let description = repo
+                .select(&DESCRIPTION_SELECTOR)
+                .next()
+                .map(|e| {
+                    e.text()
+                        .fold(String::new(), |acc, s| acc + s)
+                        .trim()
+                        .to_string()
+                })
+                .unwrap_or_default();
+
+            let stars_text = repo
+                .select(&STARS_SELECTOR)
+                .next()
+                .and_then(|e| e.parent())
+                .and_then(scraper::ElementRef::wrap)
+                .map(|e| e.text().fold(String::new(), |acc, s| acc + s))
+                .unwrap_or_default();
+            let stars = parse_count(&stars_text).unwrap_or(0);
+
+            let forks_text = repo
+                .select(&FORKS_SELECTOR)
+                .next()
+                .and_then(|e| e.parent())
+                .and_then(scraper::ElementRef::wrap)
+                .map(|e| e.text().fold(String::new(), |acc, s| acc + s))
+                .unwrap_or_default();
+            let forks = parse_count(&forks_text).unwrap_or(0);
+
+            let stars_period_text = repo
+                .select(&STARS_PERIOD_SELECTOR)
+                .next()
+                .map(|e| e.text().fold(String::new(), |acc, s| acc + s))
+                .unwrap_or_default();
+            let stars_period = stars_period_text
+                .split_whitespace()
+                .next()
+                .and_then(parse_count)
+                .unwrap_or(0);
+
+            let language = repo
+                .select(&LANGUAGE_SELECTOR)
+                .next()
+                .map(|e| {
+                    e.text()
+                        .fold(String::new(), |acc, s| acc + s)
+                        .trim()
+                        .to_string()
+                })
+                .filter(|language| !language.is_empty());
+
+            let contributors = repo
+                .select(&CONTRIBUTOR_SELECTOR)
+                .filter_map(|e| e.value().attr("href"))
+                .map(|href| sanitize_repo_segment(href.trim_matches('/')))
+                .filter(|handle| !handle.is_empty())
+                .collect();
+
+            Some(Repo {
+                author,
+                description,
+                name,
+                stars,
+                stars_period,
+                period: None,
+                forks,
+                language,
+                created_at: None,
+                topics: Vec::new(),
+                contributors,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    if repos.is_empty() {
+        return Err(FetchError::NoRepos);
+    }
+
+    Ok(repos)
+}
+
+/// Strips whitespace, which GitHub doesn't allow in names, and invisible characters like zero-width
+/// spaces, which the trending page sometimes has around names.
+fn sanitize_repo_segment(segment: &str) -> String {
+    segment
+        .chars()
+        .filter(|c| {
+            !c.is_whitespace()
+                && !c.is_control()
+                && !matches!(c, '\u{200B}'..='\u{200D}' | '\u{2060}' | '\u{FEFF}')
+        })
+        .collect()
+}
+
+/// URL of the trending page of the host, with the language percent-encoded into the path template.
+fn trending_url(
+    host: &Url,
+    path_template: &str,
+    language: &str,
+    since: Since,
+) -> Result<Url, url::ParseError> {
+    let language: String = url::form_urlencoded::byte_serialize(language.as_bytes()).collect();
+    host.join(
+        &path_template
+            .replace("{language}", &language)
+            .replace("{since}", since.as_str()),
+    )
+}
+
+const USER_AGENT: &str = concat!(
+    "rust-trending/",
+    env!("CARGO_PKG_VERSION"),
+    " (+https://github.com/pbzweihander/rust-trending)"
+);
+
+fn default_paused_key() -> String {
+    "rt:paused".to_string()
+}
+
+fn default_http_timeout_secs() -> u64 {
+    30
+}
+
+const HTTP_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Settings of every outbound HTTP client, from `http_timeout_secs` and `proxy`.
+#[derive(Clone, Debug)]
+struct HttpConfig {
+    timeout: Duration,
+    proxy: Option<reqwest::Proxy>,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(default_http_timeout_secs()),
+            proxy: None,
+        }
+    }
+}
+
+/// Set at startup before any client is built.
+static HTTP_CONFIG: OnceCell<HttpConfig> = OnceCell::new();
+
+/// Set at startup from `github.host`, as repo links are built deep in every formatter.
+static GITHUB_HOST: OnceCell<Url> = OnceCell::new();
+
+fn github_host() -> Url {
+    GITHUB_HOST
+        .get()
+        .cloned()
+        .unwrap_or_else(|| Url::parse(DEFAULT_GITHUB_HOST).unwrap())
+}
+
+fn http_config() -> HttpConfig {
+    HTTP_CONFIG.get().cloned().unwrap_or_default()
+}
+
+fn http_timeout() -> Duration {
+    http_config().timeout
+}
+
+/// Identifies itself with `USER_AGENT`, as GitHub and Reddit may block clients which don't.
+fn build_http_client(config: &HttpConfig) -> reqwest::Client {
+    let mut builder = reqwest::Client::builder()
+        .user_agent(USER_AGENT)
+        .timeout(config.timeout)
+        .connect_timeout(config.timeout.min(HTTP_CONNECT_TIMEOUT));
+    if let Some(proxy) = &config.proxy {
+        builder = builder.proxy(proxy.clone());
+    }
+    builder.build().unwrap()
+}
+
+/// Client of every request except the Twitter client, shared to reuse connections. Senders take
+/// the client as a parameter, so tests can point them at a mock server.
+static HTTP_CLIENT: Lazy<reqwest::Client> = Lazy::new(|| build_http_client(&http_config()));
+
+async fn fetch_trending_html(config: &GithubConfig, url: Url) -> reqwest::Result<String> {
+    let mut request = HTTP_CLIENT.get(url);
+    if let Some(cookie) = &config.cookie {
+        request = request.header(reqwest::header::COOKIE, cookie);
+    }
+    request.send().await?.error_for_status()?.text().await
+}
+
+/// Whether the request may succeed if tried again, unlike e.g. a 404.
+fn is_retryable(error: &reqwest::Error) -> bool {
+    error.is_timeout()
+        || error.is_connect()
+        || error.is_body()
+        || error.status().is_some_and(|status| {
+            status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+        })
+}
+
+/// Calls `fetch` until it succeeds, backing off between retryable failures.
+async fn retry_fetch<T, F, Fut>(mut fetch: F) -> reqwest::Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = reqwest::Result<T>>,
+{
+    let mut delay = FETCH_RETRY_DELAY;
+    let mut retries = 0;
+    loop {
+        match fetch().await {
+            Err(error) if retries < FETCH_RETRIES && is_retryable(&error) => {
+                retries += 1;
+                warn!(
+                    "Failed to fetch trending repos, retrying in {:?} ({}/{}): {}",
+                    delay, retries, FETCH_RETRIES, error
+                );
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+            }
+            result => return result,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct SearchRepositoriesOutput {
+    items: Vec<SearchRepository>,
+}
+
+#[derive(Deserialize, Debug)]
+struct SearchRepository {
+    name: String,
+    owner: SearchRepositoryOwner,
+    description: Option<String>,
+    stargazers_count: usize,
+    forks_count: usize,
+    language: Option<String>,
+    created_at: Option<String>,
+    #[serde(default)]
+    topics: Vec<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct SearchRepositoryOwner {
+    login: String,
+}
+
+impl From<SearchRepository> for Repo {
+    fn from(repo: SearchRepository) -> Self {
+        Self {
+            author: repo.owner.login,
+            description: repo.description.unwrap_or_default(),
+            name: repo.name,
+            stars: repo.stargazers_count,
+            stars_period: 0,
+            period: None,
+            forks: repo.forks_count,
+            language: repo.language,
+            created_at: repo
+                .created_at
+                .and_then(|created_at| OffsetDateTime::parse(&created_at, &Rfc3339).ok()),
+            topics: repo.topics,
+            contributors: Vec::new(),
+        }
+    }
+}
+
+/// Search query for repos of the language created during the range, as an approximation
+/// of the trending page.
+fn search_query(language: &str, since: Since, now: OffsetDateTime) -> String {
+    let days = match since {
+        Since::Daily => 1,
+        Since::Weekly => 7,
+        Since::Monthly => 30,
+    };
+    format!(
+        "language:{} created:>{}",
+        language,
+        (now - time::Duration::days(days)).date()
+    )
+}
+
+async fn search_repos(
+    config: &GithubConfig,
+    language: &str,
+    since: Since,
+) -> reqwest::Result<Vec<Repo>> {
+    let mut request = HTTP_CLIENT
+        .get(repo_url(
+            config.api_url().as_str(),
+            &["search", "repositories"],
+        ))
+        .header(reqwest::header::ACCEPT, "application/vnd.github+json")
+        .query(&[
+            (
+                "q",
+                search_query(language, since, OffsetDateTime::now_utc()).as_str(),
+            ),
+            ("sort", "stars"),
+            ("order", "desc"),
+            ("per_page", "25"),
+        ]);
+    if let Some(token) = &config.token {
+        request = request.bearer_auth(token);
+    }
+    let output: SearchRepositoriesOutput = request.send().await?.error_for_status()?.json().await?;
+    Ok(output.items.into_iter().map(Repo::from).collect())
+}
+
+async fn get_repo(config: &GithubConfig, author: &str, name: &str) -> reqwest::Result<Repo> {
+    let mut request = HTTP_CLIENT
+        .get(repo_url(
+            config.api_url().as_str(),
+            &["repos", author, name],
+        ))
+        .header(reqwest::header::ACCEPT, "application/vnd.github+json");
+    if let Some(token) = &config.token {
+        request = request.bearer_auth(token);
+    }
+    let repo: SearchRepository = request.send().await?.error_for_status()?.json().await?;
+    Ok(repo.into())
+}
+
+async fn fetch_repos(
+    config: &GithubConfig,
+    language: &str,
+    since: Since,
+) -> Result<Vec<Repo>, FetchError> {
+    let repos = if config.use_api {
+        retry_fetch(|| search_repos(config, language, since)).await?
+    } else {
+        let url = trending_url(
+            &config.host(),
+            config.trending_path_template(),
+            language,
+            since,
+        )?;
+        let html = retry_fetch(|| fetch_trending_html(config, url.clone())).await?;
+        let mut repos = parse_trending(html)?;
+        for repo in &mut repos {
+            repo.period = Some(since);
+        }
+        repos
+    };
+    let mut repos = dedup_repos(repos);
+    if config.backfill_descriptions && !config.use_api {
+        backfill_descriptions(config, &mut repos).await;
+    }
+    Ok(repos)
+}
+
+/// Lowercased `author/name` of repos without a description in the API either, so that they are
+/// not fetched again in every loop.
+static EMPTY_DESCRIPTIONS: Lazy<std::sync::Mutex<HashSet<String>>> = Lazy::new(Default::default);
+
+/// Fills the empty descriptions of repos from the API, as the trending page sometimes leaves out
+/// the description of a repo which has one. Failures are only logged, posting without it.
+async fn backfill_descriptions(config: &GithubConfig, repos: &mut [Repo]) {
+    for repo in repos
+        .iter_mut()
+        .filter(|repo| repo.description.trim().is_empty())
+    {
+        let slug = repo_slug(repo).to_lowercase();
+        if EMPTY_DESCRIPTIONS.lock().unwrap().contains(&slug) {
+            continue;
+        }
+        match get_repo(config, &repo.author, &repo.name).await {
+            Ok(fetched) if !fetched.description.trim().is_empty() => {
+                repo.description = fetched.description;
+            }
+            Ok(_) => {
+                EMPTY_DESCRIPTIONS.lock().unwrap().insert(slug);
+            }
+            Err(error) => warn!(
+                repo = %repo_slug(repo),
+                error = %error,
+                "failed to fetch description"
+            ),
+        }
+    }
+}
+
+/// Removes repos listed more than once, keeping the first, as they are marked posted only after
+/// being posted.
+fn dedup_repos(repos: Vec<Repo>) -> Vec<Repo> {
+    let mut seen = HashSet::new();
+    repos
+        .into_iter()
+        .filter(|repo| seen.insert((repo.author.to_lowercase(), repo.name.to_lowercase())))
+        .collect()
+}
+
+/// Fetches the OpenGraph image of the repo, retrying any failure including 404s, since the image
+/// is rendered on demand and a freshly busted cache occasionally misses.
+async fn get_github_og_image(repo: &Repo) -> Result<Bytes> {
+    let mut delay = FETCH_RETRY_DELAY;
+    let mut retries = 0;
+    loop {
+        match fetch_github_og_image(repo).await {
+            Err(error) if retries < OG_IMAGE_RETRIES => {
+                retries += 1;
+                warn!(
+                    repo = %repo_slug(repo),
+                    error = %error,
+                    "failed to fetch OpenGraph image, retrying in {:?} ({}/{})",
+                    delay,
+                    retries,
+                    OG_IMAGE_RETRIES
+                );
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+            }
+            result => return Ok(result?),
+        }
+    }
+}
+
+async fn fetch_github_og_image(repo: &Repo) -> reqwest::Result<Bytes> {
+    let url = repo_url(
+        "https://opengraph.githubassets.com",
+        &[
+            &random_string::generate(64, "0123456789abcdefghijklmnopqrstuvwxyz"),
+            &repo.author,
+            &repo.name,
+        ],
+    );
+
+    HTTP_CLIENT
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await
+}
+
+/// `author/name` of the repo, used to identify it in logs.
+fn repo_slug(repo: &Repo) -> String {
+    format!("{}/{}", repo.author, repo.name)
+}
+
+fn make_repo_title(repo: &Repo) -> String {
+    if repo.author != repo.name {
+        format!("{} / {}", repo.author, repo.name)
+    } else {
+        repo.name.clone()
+    }
+}
+
+fn make_post_prefix(repo: &Repo) -> String {
+    format!("{}: ", make_repo_title(repo))
+}
+
+fn make_post_stars(repo: &Repo, format: &FormatConfig) -> String {
+    format!(" {}", format.stars(repo.stars))
+}
+
+/// Formats the number of stars, e.g. `49626` or `49,626`.
+fn format_stars(stars: usize, format: StarFormat) -> String {
+    let digits = stars.to_string();
+    match format {
+        StarFormat::Plain => digits,
+        StarFormat::Grouped => {
+            let mut grouped = String::new();
+            for (i, digit) in digits.chars().enumerate() {
+                if i > 0 && (digits.len() - i).is_multiple_of(3) {
+                    grouped.push(',');
+                }
+                grouped.push(digit);
+            }
+            grouped
+        }
+    }
+}
+
+/// URL of the repo, with the author and name percent-encoded so that odd names can't change the
+/// path.
+fn repo_uri(repo: &Repo) -> String {
+    repo_url(github_host().as_str(), &[&repo.author, &repo.name]).into()
+}
+
+fn repo_url(base: &str, segments: &[&str]) -> Url {
+    let mut url = Url::parse(base).unwrap();
+    url.path_segments_mut()
+        .unwrap()
+        .pop_if_empty()
+        .extend(segments);
+    url
+}
+
+/// Unit in which a platform counts the length of a post.
+#[derive(Clone, Copy, Debug)]
+pub enum LengthUnit {
+    Bytes,
+    Chars,
+    Graphemes,
+}
+
+impl LengthUnit {
+    fn len(self, s: &str) -> usize {
+        match self {
+            LengthUnit::Bytes => s.len(),
+            LengthUnit::Chars => s.chars().count(),
+            LengthUnit::Graphemes => s.graphemes(true).count(),
+        }
+    }
+}
+
+/// Takes whole graphemes of `s` as long as they fit in `length`.
+fn truncate_graphemes(s: &str, length: usize, unit: LengthUnit) -> String {
+    let mut truncated = String::new();
+    let mut truncated_length = 0;
+    for grapheme in s.graphemes(true) {
+        truncated_length += unit.len(grapheme);
+        if truncated_length > length {
+            break;
+        }
+        truncated.push_str(grapheme);
+    }
+    truncated
+}
+
+/// Escapes `@` that would start a mention, like `@foo`, but not the one in `foo@bar.com`.
+fn escape_mentions(s: &str, format: &FormatConfig) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    let mut prev = None;
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        let is_word = |c: &char| c.is_alphanumeric() || *c == '_';
+        if c == '@' && !prev.as_ref().is_some_and(is_word) && chars.peek().is_some_and(is_word) {
+            escaped.push_str(format.escaped_at());
+        } else {
+            escaped.push(c);
+        }
+        prev = Some(c);
+    }
+    escaped
+}
+
+fn make_post_description(
+    repo: &Repo,
+    format: &FormatConfig,
+    length_left: usize,
+    unit: LengthUnit,
+) -> String {
+    truncate_description(&repo.description, format, length_left, unit)
+}
+
+/// Truncates the description to `length_left` including the truncation suffix.
+fn truncate_description(
+    description: &str,
+    format: &FormatConfig,
+    length_left: usize,
+    unit: LengthUnit,
+) -> String {
+    let suffix = format.truncation_suffix();
+    if unit.len(description) < length_left {
+        description.to_string()
+    } else if length_left < unit.len(suffix) {
+        String::new()
+    } else {
+        format!(
+            "{}{}",
+            truncate_graphemes(description, length_left - unit.len(suffix), unit),
+            suffix
+        )
+    }
+}
+
+/// Template of posts, used when a platform has no template configured.
+/// Available placeholders are `{title}`, `{author}`, `{name}`, `{description}`, `{star}`,
+/// `{stars}`, `{forks}`, `{language}` and `{url}`, where `{star}` is the configured star symbol.
+pub const DEFAULT_TEMPLATE: &str = "{title}: {description} {star}{stars} {url}";
+pub const DEFAULT_TEMPLATE_WITHOUT_STARS: &str = "{title}: {description} {url}";
+
+pub fn default_template(show_stars: bool) -> &'static str {
+    if show_stars {
+        DEFAULT_TEMPLATE
+    } else {
+        DEFAULT_TEMPLATE_WITHOUT_STARS
+    }
+}
+
+/// Config of a platform whose posts are rendered from a template.
+trait TemplateConfig {
+    fn custom_template(&self) -> Option<&str>;
+    fn show_stars(&self) -> bool;
+
+    /// The template of the platform, or the default one.
+    fn template(&self) -> &str {
+        self.custom_template()
+            .unwrap_or(default_template(self.show_stars()))
+    }
+}
+
+impl TemplateConfig for TwitterConfig {
+    fn custom_template(&self) -> Option<&str> {
+        self.template.as_deref()
+    }
+
+    fn show_stars(&self) -> bool {
+        self.show_stars
+    }
+}
+
+impl TemplateConfig for MastodonConfig {
+    fn custom_template(&self) -> Option<&str> {
+        self.template.as_deref()
+    }
+
+    fn show_stars(&self) -> bool {
+        self.show_stars
+    }
+}
+
+impl TemplateConfig for BlueskyConfig {
+    fn custom_template(&self) -> Option<&str> {
+        self.template.as_deref()
+    }
+
+    fn show_stars(&self) -> bool {
+        self.show_stars
+    }
+}
+
+impl TemplateConfig for WebhookConfig {
+    fn custom_template(&self) -> Option<&str> {
+        self.template.as_deref()
+    }
+
+    fn show_stars(&self) -> bool {
+        self.show_stars
+    }
+}
+
+impl TemplateConfig for NostrConfig {
+    fn custom_template(&self) -> Option<&str> {
+        self.template.as_deref()
+    }
+
+    fn show_stars(&self) -> bool {
+        self.show_stars
+    }
+}
+
+/// Optional parts appended to the posts of a platform, between the template and the hashtags.
+/// A table of its own rather than flattened into the platform config, which would hide misspelled
+/// keys of the platform from `read_config`.
+#[derive(Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct PostExtras {
+    /// ` ⑂N` with the number of forks.
+    pub show_forks: bool,
+    /// ` #Lang` with the primary language of the repo, if known.
+    pub show_language: bool,
+    /// ` (+N today)` with the stars gained during the trending range, if known.
+    pub show_period_stars: bool,
+}
+
+/// Renders the enabled extras, each with a leading space. Passed along with the hashtags, so
+/// they count toward the length limit of the platform.
+pub fn make_post_extras(repo: &Repo, extras: &PostExtras, format: &FormatConfig) -> String {
+    let mut rendered = String::new();
+    if extras.show_forks {
+        rendered.push_str(&format!(
+            " ⑂{}",
+            format_stars(repo.forks, format.star_format)
+        ));
+    }
+    if extras.show_language {
+        // Hashtags can't contain symbols, e.g. "C++" or "Jupyter Notebook"
+        let language: String = repo
+            .language
+            .as_deref()
+            .unwrap_or_default()
+            .replace('+', "Plus")
+            .replace('#', "Sharp")
+            .chars()
+            .filter(|c| c.is_alphanumeric())
+            .collect();
+        if !language.is_empty() {
+            rendered.push_str(&format!(" #{}", language));
+        }
+    }
+    if extras.show_period_stars && repo.stars_period > 0 {
+        if let Some(period) = repo.period {
+            rendered.push_str(&format!(
+                " (+{} {})",
+                format_stars(repo.stars_period, format.star_format),
+                period.period_label()
+            ));
+        }
+    }
+    rendered
+}
+
+fn template_value(repo: &Repo, format: &FormatConfig, placeholder: &str) -> Option<String> {
+    Some(match placeholder {
+        "title" => make_repo_title(repo),
+        "author" => repo.author.clone(),
+        "name" => repo.name.clone(),
+        "description" => repo.description.clone(),
+        "star" => format.star_symbol().to_string(),
+        "stars" => format_stars(repo.stars, format.star_format),
+        "forks" => repo.forks.to_string(),
+        "url" => repo_uri(repo),
+        "language" => repo.language.clone().unwrap_or_default(),
+        _ => return None,
+    })
+}
+
+/// Replaces the placeholders of the template in a single pass, so that values containing
+/// placeholders are not expanded again. Unknown placeholders are kept as they are.
+/// A hashtag like `#{language}` is left out along with a space before it when the repo has no
+/// language, rather than rendered as a bare `#`.
+fn expand_template(template: &str, mut value: impl FnMut(&str) -> Option<String>) -> String {
+    let mut expanded = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        expanded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        match rest
+            .find('}')
+            .and_then(|end| Some((end, value(&rest[1..end])?)))
+        {
+            Some((end, value)) => {
+                let hashtag = expanded.ends_with('#') && &rest[1..end] == "language";
+                rest = &rest[end + 1..];
+                if hashtag && value.is_empty() {
+                    expanded.pop();
+                    if expanded.ends_with(' ') && (rest.is_empty() || rest.starts_with(' ')) {
+                        expanded.pop();
+                    }
+                }
+                expanded.push_str(&value);
+            }
+            None => {
+                expanded.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    expanded.push_str(rest);
+    expanded
+}
+
+/// Renders the template within `length`, truncating only the description to fit.
+/// If the post does not fit even without the description, the title is truncated too.
+/// `url_length` is the length of URLs for platforms counting them as a fixed length.
+/// `hashtags` is appended after the rendered template.
+/// `escape_mentions` escapes `@` in the description for platforms where it would mention someone.
+#[allow(clippy::too_many_arguments)]
+fn render_post(
+    template: &str,
+    repo: &Repo,
+    format: &FormatConfig,
+    length: usize,
+    unit: LengthUnit,
+    url_length: Option<usize>,
+    hashtags: &str,
+    escape_mentions: bool,
+) -> String {
+    let count = |placeholder: &str| template.matches(placeholder).count();
+    let fixed = expand_template(template, |placeholder| match placeholder {
+        "description" => Some(String::new()),
+        "url" if url_length.is_some() => Some(String::new()),
+        _ => template_value(repo, format, placeholder),
+    });
+    let fixed_length = unit.len(&fixed)
+        + url_length.map_or(0, |url_length| url_length * count("{url}"))
+        + unit.len(hashtags);
+
+    let mut title = make_repo_title(repo);
+    let mut length_left = length.saturating_sub(fixed_length) / count("{description}").max(1);
+    if fixed_length > length && count("{title}") > 0 {
+        let suffix = "...";
+        let overflow = (fixed_length - length).div_ceil(count("{title}"));
+        let title_length = unit.len(&title).saturating_sub(overflow + unit.len(suffix));
+        title = format!(
+            "{}{}",
+            truncate_graphemes(&title, title_length, unit),
+            suffix
+        );
+        length_left = 0;
+    }
+    let description = if escape_mentions {
+        truncate_description(
+            &self::escape_mentions(&repo.description, format),
+            format,
+            length_left,
+            unit,
+        )
+    } else {
+        make_post_description(repo, format, length_left, unit)
+    };
+
+    // Without a description, e.g. when the title takes all the room, the separators around it would
+    // be left dangling, like `aaa...:  ★123`
+    let template = if description.is_empty() {
+        template
+            .replace(": {description} ", " ")
+            .replace(": {description}", "")
+            .replace("{description} ", "")
+    } else {
+        template.to_string()
+    };
+    let post = expand_template(&template, |placeholder| match placeholder {
+        "title" => Some(title.clone()),
+        "description" => Some(description.clone()),
+        _ => template_value(repo, format, placeholder),
+    });
+    format!("{}{}", post, hashtags)
+}
+
+/// Makes hashtags to append to posts, with or without the leading `#` in the config.
+pub fn make_hashtags(hashtags: &[String]) -> String {
+    let mut seen = Vec::new();
+    for hashtag in hashtags {
+        let hashtag = hashtag.trim().trim_start_matches('#');
+        if !hashtag.is_empty() && !seen.contains(&hashtag) {
+            seen.push(hashtag);
+        }
+    }
+    seen.iter()
+        .map(|hashtag| format!(" #{}", hashtag))
+        .collect()
+}
+
+pub fn make_tweet(repo: &Repo, template: &str, hashtags: &str, format: &FormatConfig) -> String {
+    // Twitter counts most non-ASCII characters as two, so bytes never exceed the limit
+    render_post(
+        template,
+        repo,
+        format,
+        TWEET_LENGTH,
+        LengthUnit::Bytes,
+        None,
+        hashtags,
+        true,
+    )
+}
+
+/// The content warning counts toward the length limit of Mastodon as well.
+pub fn make_toot(
+    repo: &Repo,
+    template: &str,
+    hashtags: &str,
+    content_warning: Option<&str>,
+    format: &FormatConfig,
+) -> String {
+    // Mastodon counts characters by code point, and URLs as a fixed length
+    let content_warning_length = content_warning.map_or(0, |cw| cw.chars().count());
+    render_post(
+        template,
+        repo,
+        format,
+        TOOT_LENGTH.saturating_sub(content_warning_length),
+        LengthUnit::Chars,
+        Some(MASTODON_FIXED_URL_LENGTH),
+        hashtags,
+        true,
+    )
+}
+
+/// Prefix of redis keys, so that each language and range is deduplicated separately.
+/// Rust and daily range have no prefix to keep compatibility with existing keys.
+fn key_prefix(language: &str, since: Since) -> String {
+    let mut prefix = String::new();
+    if !language.eq_ignore_ascii_case("rust") {
+        prefix.push_str(&language.to_lowercase());
+        prefix.push(':');
+    }
+    if since != Since::Daily {
+        prefix.push_str(since.as_str());
+        prefix.push(':');
+    }
+    prefix
+}
+
+/// Key of a posted repo, lowercased as GitHub ignores the case of authors and names.
+fn repo_key(prefix: &str, repo: &Repo) -> String {
+    format!(
+        "{}{}/{}",
+        prefix,
+        repo.author.to_lowercase(),
+        repo.name.to_lowercase()
+    )
+}
+
+/// Each Mastodon instance is tracked separately, e.g. `mastodon:fosstodon.org`.
+fn mastodon_platform_name(config: &MastodonConfig) -> String {
+    format!(
+        "mastodon:{}",
+        config.instance_url.host_str().unwrap_or_default()
+    )
+}
+
+/// Key of a repo posted to a single platform, e.g. `mastodon:author/name`.
+fn platform_key(platform: &str, prefix: &str, repo: &Repo) -> String {
+    format!("{}:{}", platform, repo_key(prefix, repo))
+}
+
+/// Escapes `&`, `<` and `>`, which is enough for both Telegram HTML and Slack mrkdwn.
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+pub fn make_telegram_message(repo: &Repo, format: &FormatConfig) -> String {
+    let prefix = make_post_prefix(repo);
+    let stars = make_post_stars(repo, format);
+
+    let length_left = TELEGRAM_POST_LENGTH.saturating_sub(prefix.len() + stars.len());
+
+    let description = make_post_description(repo, format, length_left, LengthUnit::Graphemes);
+
+    format!(
+        "<a href=\"{}\">{}</a>: {}{}",
+        repo_uri(repo),
+        escape_html(&make_repo_title(repo)),
+        escape_html(&description),
+        stars
+    )
+}
+
+/// Storage of posted repos, so that a repo is not posted again until its key expires.
+#[async_trait]
+trait Store: Send {
+    async fn is_posted(&mut self, key: &str) -> Result<bool>;
+    async fn posted_value(&mut self, key: &str) -> Result<Option<String>>;
+    async fn mark_posted(&mut self, key: &str, value: &str, ttl: usize) -> Result<()>;
+    /// Sets the key to the UNIX timestamp, without expiry.
+    async fn set_heartbeat(&mut self, key: &str, timestamp: u64) -> Result<()>;
+    /// Returns the keys and values of every deadlettered post.
+    async fn deadletters(&mut self) -> Result<Vec<(String, String)>>;
+    async fn deadletter(&mut self, key: &str) -> Result<Option<String>>;
+    /// Inserts or replaces the deadlettered post of the key. A new key is not inserted when there
+    /// are `max_len` posts already, returning false.
+    async fn set_deadletter(&mut self, key: &str, value: &str, max_len: usize) -> Result<bool>;
+    async fn remove_deadletter(&mut self, key: &str) -> Result<()>;
+    /// Reads a key set by operators, like `paused_key`.
+    async fn flag(&mut self, key: &str) -> Result<Option<String>>;
+}
+
+struct RedisStore {
+    client: redis::Client,
+    conn: redis::aio::MultiplexedConnection,
+    key_prefix: String,
+}
+
+/// Whether the connection is lost, unlike e.g. a wrong type of value.
+fn is_redis_connection_error(error: &redis::RedisError) -> bool {
+    error.is_connection_dropped()
+        || error.is_connection_refusal()
+        || error.is_io_error()
+        || error.is_timeout()
+}
+
+impl RedisStore {
+    async fn connect(config: &RedisConfig) -> Result<Self> {
+        let client =
+            redis::Client::open(config.url.as_str()).context("While creating redis client")?;
+        let conn = client
+            .get_multiplexed_tokio_connection()
+            .await
+            .context("While connecting redis")?;
+        Ok(Self {
+            client,
+            conn,
+            key_prefix: config.key_prefix.clone(),
+        })
+    }
+
+    /// Runs the command, reconnecting with backoff when the connection is lost, so that a short
+    /// outage of redis doesn't fail the whole loop.
+    async fn query<T: redis::FromRedisValue>(&mut self, cmd: &redis::Cmd) -> Result<T> {
+        let mut delay = REDIS_RECONNECT_DELAY;
+        let mut retries = 0;
+        loop {
+            match cmd.query_async(&mut self.conn).await {
+                Err(error)
+                    if retries < REDIS_RECONNECT_RETRIES && is_redis_connection_error(&error) =>
+                {
+                    retries += 1;
+                    warn!(
+                        error = %error,
+                        "lost connection to redis, reconnecting in {:?} ({}/{})",
+                        delay,
+                        retries,
+                        REDIS_RECONNECT_RETRIES
+                    );
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                    match self.client.get_multiplexed_tokio_connection().await {
+                        Ok(conn) => self.conn = conn,
+                        Err(error) => warn!(error = %error, "failed to reconnect to redis"),
+                    }
+                }
+                result => return Ok(result?),
+            }
+        }
+    }
+}
+
+/// Returns `author/name` of a key written by versions before keys of each platform, which are
+/// neither namespaced by a platform nor by a language or a range.
+fn legacy_repo_key<'a>(key_prefix: &str, key: &'a str) -> Option<&'a str> {
+    let key = key.strip_prefix(key_prefix)?;
+    let (author, name) = key.split_once('/')?;
+    if author.is_empty() || name.is_empty() || name.contains('/') || key.contains(':') {
+        return None;
+    }
+    Some(key)
+}
+
+/// Whether the value of a posted repo was written by versions before keys of each platform, which
+/// only hold the timestamp. Newer keys of repos in the default range look like older ones, but hold
+/// a `PostedRecord` of the platforms actually posted.
+fn is_legacy_posted_value(value: &str) -> bool {
+    value.parse::<u64>().is_ok()
+}
+
+impl RedisStore {
+    /// Copies keys of posted repos written by older versions to the key of each platform, keeping
+    /// the remaining TTL. Existing keys are not overwritten, so this can be run more than once.
+    /// The old keys are kept until they expire, as they mark the repo posted even to platforms
+    /// configured after migrating.
+    async fn migrate_legacy_keys(&mut self, platforms: &[String]) -> Result<usize> {
+        let pattern = format!("{}*/*", self.key_prefix);
+        let mut legacy_keys = Vec::new();
+        let mut cursor = 0u64;
+        loop {
+            let (next, keys): (u64, Vec<String>) = self
+                .query(
+                    redis::cmd("SCAN")
+                        .arg(cursor)
+                        .arg("MATCH")
+                        .arg(&pattern)
+                        .arg("COUNT")
+                        .arg(1000),
+                )
+                .await
+                .context("While scanning keys")?;
+            legacy_keys.extend(
+                keys.iter()
+                    .filter_map(|key| legacy_repo_key(&self.key_prefix, key))
+                    .map(str::to_string),
+            );
+            if next == 0 {
+                break;
+            }
+            cursor = next;
+        }
+
+        let mut migrated = 0;
+        for key in legacy_keys {
+            let full_key = format!("{}{}", self.key_prefix, key);
+            let value: Option<String> = self.query(&redis::Cmd::get(&full_key)).await?;
+            let ttl: i64 = self.query(&redis::Cmd::ttl(&full_key)).await?;
+            // Expired since scanned, without an expiry which this bot never writes, or written by
+            // this version
+            let value = match value {
+                Some(value) if ttl > 0 && is_legacy_posted_value(&value) => value,
+                _ => continue,
+            };
+            for platform in platforms {
+                let platform_key =
+                    format!("{}{}:{}", self.key_prefix, platform, key.to_lowercase());
+                self.query::<()>(
+                    redis::cmd("SET")
+                        .arg(&platform_key)
+                        .arg(&value)
+                        .arg("EX")
+                        .arg(ttl)
+                        .arg("NX"),
+                )
+                .await?;
+            }
+            migrated += 1;
+        }
+        Ok(migrated)
+    }
+}
+
+#[async_trait]
+impl Store for RedisStore {
+    async fn is_posted(&mut self, key: &str) -> Result<bool> {
+        let key = format!("{}{}", self.key_prefix, key);
+        self.query(&redis::Cmd::exists(key)).await
+    }
+
+    async fn posted_value(&mut self, key: &str) -> Result<Option<String>> {
+        let key = format!("{}{}", self.key_prefix, key);
+        self.query(&redis::Cmd::get(key)).await
+    }
+
+    async fn mark_posted(&mut self, key: &str, value: &str, ttl: usize) -> Result<()> {
+        let key = format!("{}{}", self.key_prefix, key);
+        self.query(&redis::Cmd::set_ex(key, value, ttl)).await
+    }
+
+    async fn set_heartbeat(&mut self, key: &str, timestamp: u64) -> Result<()> {
+        let key = format!("{}{}", self.key_prefix, key);
+        self.query(&redis::Cmd::set(key, timestamp)).await
+    }
+
+    async fn deadletters(&mut self) -> Result<Vec<(String, String)>> {
+        let hash = format!("{}{}", self.key_prefix, DEADLETTER_KEY);
+        let values: BTreeMap<String, String> = self.query(&redis::Cmd::hgetall(hash)).await?;
+        Ok(values.into_iter().collect())
+    }
+
+    async fn deadletter(&mut self, key: &str) -> Result<Option<String>> {
+        let hash = format!("{}{}", self.key_prefix, DEADLETTER_KEY);
+        self.query(&redis::Cmd::hget(hash, key)).await
+    }
+
+    async fn set_deadletter(&mut self, key: &str, value: &str, max_len: usize) -> Result<bool> {
+        let hash = format!("{}{}", self.key_prefix, DEADLETTER_KEY);
+        if !self.query::<bool>(&redis::Cmd::hexists(&hash, key)).await?
+            && self.query::<usize>(&redis::Cmd::hlen(&hash)).await? >= max_len
+        {
+            return Ok(false);
+        }
+        self.query::<()>(&redis::Cmd::hset(&hash, key, value))
+            .await?;
+        Ok(true)
+    }
+
+    async fn remove_deadletter(&mut self, key: &str) -> Result<()> {
+        let hash = format!("{}{}", self.key_prefix, DEADLETTER_KEY);
+        self.query(&redis::Cmd::hdel(hash, key)).await
+    }
+
+    async fn flag(&mut self, key: &str) -> Result<Option<String>> {
+        let key = format!("{}{}", self.key_prefix, key);
+        self.query(&redis::Cmd::get(key)).await
+    }
+}
+
+struct SqliteStore {
+    conn: rusqlite::Connection,
+}
+
+impl SqliteStore {
+    fn open(config: &SqliteConfig) -> Result<Self> {
+        let conn = rusqlite::Connection::open(&config.path).context("While opening sqlite")?;
+        Self::new(conn)
+    }
+
+    fn new(conn: rusqlite::Connection) -> Result<Self> {
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS posted_repos (
+                key TEXT PRIMARY KEY NOT NULL,
+                value TEXT NOT NULL,
+                posted_at INTEGER NOT NULL,
+                expires_at INTEGER NOT NULL
+            )",
+            [],
+        )?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS heartbeats (
+                key TEXT PRIMARY KEY NOT NULL,
+                timestamp INTEGER NOT NULL
+            )",
+            [],
+        )?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS flags (
+                key TEXT PRIMARY KEY NOT NULL,
+                value TEXT NOT NULL
+            )",
+            [],
+        )?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS deadletters (
+                key TEXT PRIMARY KEY NOT NULL,
+                value TEXT NOT NULL
+            )",
+            [],
+        )?;
+        Ok(Self { conn })
+    }
+}
+
+#[async_trait]
+impl Store for SqliteStore {
+    async fn is_posted(&mut self, key: &str) -> Result<bool> {
+        Ok(self.conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM posted_repos WHERE key = ?1 AND expires_at > ?2)",
+            rusqlite::params![key, now_ts() as i64],
+            |row| row.get(0),
+        )?)
+    }
+
+    async fn posted_value(&mut self, key: &str) -> Result<Option<String>> {
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT value FROM posted_repos WHERE key = ?1 AND expires_at > ?2",
+                rusqlite::params![key, now_ts() as i64],
+                |row| row.get(0),
+            )
+            .optional()?)
+    }
+
+    async fn mark_posted(&mut self, key: &str, value: &str, ttl: usize) -> Result<()> {
+        let now = now_ts() as i64;
+        // Expired rows are never read again, so clean them up like redis does
+        self.conn.execute(
+            "DELETE FROM posted_repos WHERE expires_at <= ?1",
+            rusqlite::params![now],
+        )?;
+        self.conn.execute(
+            "INSERT OR REPLACE INTO posted_repos (key, value, posted_at, expires_at)
+            VALUES (?1, ?2, ?3, ?4)",
+            rusqlite::params![key, value, now, now + ttl as i64],
+        )?;
+        Ok(())
+    }
+
+    async fn set_heartbeat(&mut self, key: &str, timestamp: u64) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO heartbeats (key, timestamp) VALUES (?1, ?2)",
+            rusqlite::params![key, timestamp as i64],
+        )?;
+        Ok(())
+    }
+
+    async fn deadletters(&mut self) -> Result<Vec<(String, String)>> {
+        Ok(self
+            .conn
+            .prepare("SELECT key, value FROM deadletters")?
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<_>>()?)
+    }
+
+    async fn deadletter(&mut self, key: &str) -> Result<Option<String>> {
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT value FROM deadletters WHERE key = ?1",
+                rusqlite::params![key],
+                |row| row.get(0),
+            )
+            .optional()?)
+    }
+
+    async fn set_deadletter(&mut self, key: &str, value: &str, max_len: usize) -> Result<bool> {
+        let inserted = self.conn.execute(
+            "INSERT OR REPLACE INTO deadletters (key, value)
+            SELECT ?1, ?2
+            WHERE EXISTS(SELECT 1 FROM deadletters WHERE key = ?1)
+                OR (SELECT COUNT(*) FROM deadletters) < ?3",
+            rusqlite::params![key, value, max_len as i64],
+        )?;
+        Ok(inserted > 0)
+    }
+
+    async fn remove_deadletter(&mut self, key: &str) -> Result<()> {
+        self.conn.execute(
+            "DELETE FROM deadletters WHERE key = ?1",
+            rusqlite::params![key],
+        )?;
+        Ok(())
+    }
+
+    async fn flag(&mut self, key: &str) -> Result<Option<String>> {
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT value FROM flags WHERE key = ?1",
+                rusqlite::params![key],
+                |row| row.get(0),
+            )
+            .optional()?)
+    }
+}
+
+/// Whether the value of a flag turns it on, unlike e.g. `0` or `false`.
+fn is_truthy(value: &str) -> bool {
+    let value = value.trim();
+    !value.is_empty()
+        && value != "0"
+        && !value.eq_ignore_ascii_case("false")
+        && !value.eq_ignore_ascii_case("no")
+        && !value.eq_ignore_ascii_case("off")
+}
+
+const DEADLETTER_KEY: &str = "rt:deadletter";
+/// Failed posts beyond this are dropped, so that a long outage doesn't grow the hash forever.
+const DEADLETTER_MAX_LEN: usize = 1000;
+/// Failed posts are retried this many times before giving up.
+const DEADLETTER_MAX_ATTEMPTS: u32 = 5;
+/// Delay before the first retry of a failed post, doubled on each retry.
+const DEADLETTER_RETRY_DELAY: Duration = Duration::from_secs(10 * 60);
+/// Failed posts retried in a single loop, so that they don't hold back the fetch for long.
+const DEADLETTER_RETRIES_PER_LOOP: usize = 3;
+
+/// A post which failed on a platform, kept under the key of the platform to be retried even if
+/// the repo is no longer trending by then.
+#[derive(Deserialize, Serialize, Debug, PartialEq)]
+struct DeadLetter {
+    prefix: String,
+    repo: Repo,
+    /// Not serialized with the repo, but still needed by the extras and denylists.
+    #[serde(default)]
+    period: Option<Since>,
+    #[serde(default)]
+    created_at: Option<i64>,
+    platform: String,
+    error: String,
+    /// Number of retries failed so far.
+    attempts: u32,
+    failed_at: u64,
+}
+
+impl DeadLetter {
+    fn retry_at(&self) -> u64 {
+        self.failed_at + DEADLETTER_RETRY_DELAY.as_secs() * 2u64.pow(self.attempts)
+    }
+
+    fn into_repo(self) -> Repo {
+        Repo {
+            period: self.period,
+            created_at: self
+                .created_at
+                .and_then(|timestamp| OffsetDateTime::from_unix_timestamp(timestamp).ok()),
+            ..self.repo
+        }
+    }
+}
+
+const HEARTBEAT_LAST_FETCH: &str = "rt:last_fetch";
+const HEARTBEAT_LAST_POST: &str = "rt:last_post";
+
+/// Records the time of the last fetch or post in the store, for healthchecks and dashboards
+/// reading it directly. Failures are only logged, as posting doesn't depend on them.
+async fn record_heartbeat(config: &Config, store: &mut dyn Store, key: &str) {
+    if config.dry_run {
+        return;
+    }
+    if let Err(error) = store.set_heartbeat(key, now_ts()).await {
+        warn!(key, error = %format!("{:#}", error), "failed to record heartbeat");
+    }
+}
+
+async fn open_store(config: &Config) -> Result<Box<dyn Store>> {
+    if let Some(config) = &config.sqlite {
+        return Ok(Box::new(SqliteStore::open(config)?));
+    }
+    let config = config.redis.as_ref().context("No store configured")?;
+    Ok(Box::new(RedisStore::connect(config).await?))
+}
+
+/// Returns the key, either of the repo or of a platform, as it was written before keys were
+/// lowercased, i.e. with the repo as displayed. `None` when it is the same key.
+fn cased_key(key: &str, repo: &Repo) -> Option<String> {
+    let slug = format!("{}/{}", repo.author, repo.name);
+    let cased_key = format!("{}{}", key.strip_suffix(&slug.to_lowercase())?, slug);
+    Some(cased_key).filter(|cased_key| cased_key != key)
+}
+
+/// Whether the key of the repo is posted, under either the lowercased or the cased key.
+async fn is_key_posted(store: &mut dyn Store, key: &str, repo: &Repo) -> Result<bool> {
+    if store.is_posted(key).await? {
+        return Ok(true);
+    }
+    match cased_key(key, repo) {
+        Some(cased_key) => store.is_posted(&cased_key).await,
+        None => Ok(false),
+    }
+}
+
+async fn is_repo_posted(store: &mut dyn Store, prefix: &str, repo: &Repo) -> Result<bool> {
+    is_key_posted(store, &repo_key(prefix, repo), repo).await
+}
+
+/// Stored under the key of a posted repo.
+#[derive(Deserialize, Serialize, Debug, PartialEq, Eq)]
+struct PostedRecord {
+    posted_at: u64,
+    stars_at_post: usize,
+    platforms: Vec<String>,
+}
+
+/// Reads back what is stored for a posted repo. Keys written by older versions only hold the
+/// timestamp, so they have no record.
+async fn get_posted_record(
+    store: &mut dyn Store,
+    prefix: &str,
+    repo: &Repo,
+) -> Result<Option<PostedRecord>> {
+    get_posted_key_record(store, &repo_key(prefix, repo), repo).await
+}
+
+async fn get_posted_key_record(
+    store: &mut dyn Store,
+    key: &str,
+    repo: &Repo,
+) -> Result<Option<PostedRecord>> {
+    let mut value = store.posted_value(key).await?;
+    if value.is_none() {
+        if let Some(cased_key) = cased_key(key, repo) {
+            value = store.posted_value(&cased_key).await?;
+        }
+    }
+    Ok(value.and_then(|value| serde_json::from_str(&value).ok()))
+}
+
+/// Returns the stars a posted repo must have been posted with to count as posted, when the repo
+/// grew enough since its last post to be posted again.
+fn repost_threshold(record: &PostedRecord, repo: &Repo, star_delta: usize) -> Option<usize> {
+    let threshold = record.stars_at_post + star_delta;
+    if repo.stars >= threshold {
+        Some(threshold)
+    } else {
+        None
+    }
+}
+
+/// Parses a Retry-After header value, either in seconds or as an HTTP date.
+fn parse_retry_after(value: &str, now: OffsetDateTime) -> Option<Duration> {
+    if let Ok(seconds) = value.trim().parse() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = OffsetDateTime::parse(value.trim(), &Rfc2822).ok()?;
+    (date - now).try_into().ok()
+}
+
+/// Failure of a post to a platform, telling whether posting again may succeed.
+#[derive(Debug, thiserror::Error)]
+pub enum PostError {
+    #[error("Timed out posting to {0}")]
+    Timeout(&'static str),
+    /// Rejected with a client error other than rate limits, like an invalid token or a post too
+    /// long, which fails again when retried.
+    #[error("{0:#}")]
+    Rejected(anyhow::Error),
+    #[error("{0:#}")]
+    Other(anyhow::Error),
+}
+
+/// Returns the status of a response error of any client used to post, i.e. reqwest, the Twitter
+/// client and the XRPC client of Bluesky.
+fn response_error_status(error: &(dyn std::error::Error + 'static)) -> Option<reqwest::StatusCode> {
+    fn xrpc_status<E: std::fmt::Debug + 'static>(
+        error: &(dyn std::error::Error + 'static),
+    ) -> Option<reqwest::StatusCode> {
+        match error.downcast_ref::<atrium_xrpc::error::Error<E>>()? {
+            atrium_xrpc::error::Error::XrpcResponse(error) => Some(error.status),
+            _ => None,
+        }
+    }
+
+    if let Some(error) = error.downcast_ref::<reqwest::Error>() {
+        return error.status();
+    }
+    if let Some(error) = error.downcast_ref::<twitter_v2::Error>() {
+        return match error {
+            twitter_v2::Error::Api(error) => Some(error.status),
+            twitter_v2::Error::Request(error) => error.status(),
+            _ => None,
+        };
+    }
+    xrpc_status::<atproto::repo::create_record::Error>(error)
+        .or_else(|| xrpc_status::<atproto::repo::upload_blob::Error>(error))
+        .or_else(|| xrpc_status::<atproto::server::create_session::Error>(error))
+}
+
+/// Returns the status of the first response error in the chain.
+fn error_status(error: &anyhow::Error) -> Option<reqwest::StatusCode> {
+    error.chain().find_map(response_error_status)
+}
+
+impl From<anyhow::Error> for PostError {
+    fn from(error: anyhow::Error) -> Self {
+        let rejected = error
+            .chain()
+            .filter_map(response_error_status)
+            .any(|status| {
+                status.is_client_error() && status != reqwest::StatusCode::TOO_MANY_REQUESTS
+            });
+        if rejected {
+            PostError::Rejected(error)
+        } else {
+            PostError::Other(error)
+        }
+    }
+}
+
+impl PostError {
+    pub fn is_retryable(&self) -> bool {
+        !matches!(self, PostError::Rejected(_))
+    }
+
+    /// Status of the response the post was rejected with, if any.
+    fn status(&self) -> Option<reqwest::StatusCode> {
+        match self {
+            PostError::Timeout(_) => None,
+            PostError::Rejected(error) | PostError::Other(error) => error_status(error),
+        }
+    }
+
+    /// Label of the error in metrics.
+    fn kind(&self) -> &'static str {
+        match self {
+            PostError::Timeout(_) => "timeout",
+            PostError::Rejected(_) => "rejected",
+            PostError::Other(_) => "other",
+        }
+    }
+}
+
+/// Sends the request and checks its status, retrying when rate limited. The URL is left out of
+/// errors and logs, as it holds the token of webhooks and bots.
+async fn send_with_rate_limit(request: reqwest::RequestBuilder) -> Result<reqwest::Response> {
+    let mut retries = 0;
+    loop {
+        let response = request
+            .try_clone()
+            .context("Request is not cloneable")?
+            .send()
+            .await
+            .map_err(reqwest::Error::without_url)?;
+        if response.status() != reqwest::StatusCode::TOO_MANY_REQUESTS
+            || retries >= RATE_LIMIT_RETRIES
+        {
+            return Ok(response
+                .error_for_status()
+                .map_err(reqwest::Error::without_url)?);
+        }
+
+        let delay = response
+            .headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| parse_retry_after(value, OffsetDateTime::now_utc()))
+            .unwrap_or(RATE_LIMIT_DEFAULT_DELAY)
+            .min(RATE_LIMIT_MAX_DELAY);
+        retries += 1;
+        warn!(
+            "Rate limited by {}, retrying in {:?} ({}/{})",
+            response.url().host_str().unwrap_or_default(),
+            delay,
+            retries,
+            RATE_LIMIT_RETRIES
+        );
+        tokio::time::sleep(delay).await;
+    }
+}
+
+async fn tweet(config: &TwitterConfig, content: String) -> Result<()> {
+    let token = Oauth1aToken::new(
+        &config.consumer_key,
+        &config.consumer_secret,
+        &config.token,
+        &config.secret,
+    );
+    TwitterApi::new(token)
+        .post_tweet()
+        .text(content)
+        .send()
+        .await?;
+    Ok(())
+}
+
+#[derive(Serialize, Debug)]
+struct PostStatusesBody<'a> {
+    status: &'a str,
+    visibility: &'a str,
+    #[serde(rename = "media_ids[]", skip_serializing_if = "Option::is_none")]
+    media_id: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    spoiler_text: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    in_reply_to_id: Option<&'a str>,
+    /// Returns a scheduled status instead, whose ID is not of a status.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scheduled_at: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Status {
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct MediaAttachment {
+    id: String,
+}
+
+fn make_image_description(repo: &Repo) -> String {
+    format!("Social preview for {}/{}", repo.author, repo.name)
+}
+
+async fn upload_mastodon_media(
+    client: &reqwest::Client,
+    config: &MastodonConfig,
+    repo: &Repo,
+) -> Result<String> {
+    let image = get_github_og_image(repo).await?;
+    let url = config.instance_url.join("./api/v2/media")?;
+    let form = reqwest::multipart::Form::new()
+        .part(
+            "file",
+            reqwest::multipart::Part::bytes(image.to_vec())
+                .file_name("preview.png")
+                .mime_str("image/png")?,
+        )
+        .text("description", make_image_description(repo));
+    // Multipart bodies can't be cloned, so uploads are not retried on rate limits
+    let media: MediaAttachment = client
+        .post(url)
+        .bearer_auth(&config.access_token)
+        .multipart(form)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    Ok(media.id)
+}
+
+/// Makes the Idempotency-Key of a toot, so that Mastodon ignores retries of a toot it already
+/// created. The key is the same for a repo on the same instance during a day.
+fn make_idempotency_key(instance_url: &Url, repo: &Repo, date: Date) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(instance_url.as_str());
+    hasher.update(repo_key("", repo));
+    hasher.update(date.to_string());
+    format!("{:x}", hasher.finalize())
+}
+
+/// Posts the status, returning its ID.
+async fn create_status(
+    client: &reqwest::Client,
+    config: &MastodonConfig,
+    body: &PostStatusesBody<'_>,
+    idempotency_key: Option<String>,
+) -> Result<String> {
+    let url = config.instance_url.join("./api/v1/statuses")?;
+    let mut request = client
+        .post(url)
+        .bearer_auth(&config.access_token)
+        .form(body);
+    if let Some(idempotency_key) = idempotency_key {
+        request = request.header("Idempotency-Key", idempotency_key);
+    }
+    let status: Status = send_with_rate_limit(request).await?.json().await?;
+    Ok(status.id)
+}
+
+/// Last scheduled time of each Mastodon instance with `schedule_spread`, kept across loops.
+static MASTODON_SCHEDULES: Lazy<std::sync::Mutex<HashMap<String, OffsetDateTime>>> =
+    Lazy::new(Default::default);
+
+/// Time to schedule a status at, a `max_per_day`th of the window after the `previous` one and no
+/// sooner than Mastodon allows. `None` when the next window is full, i.e. it would be scheduled in
+/// a later one.
+fn next_scheduled_at(
+    start: Time,
+    end: Time,
+    max_per_day: u32,
+    previous: Option<OffsetDateTime>,
+    now: OffsetDateTime,
+) -> Option<OffsetDateTime> {
+    let window = |date: Date| {
+        (
+            date.with_time(start).assume_utc(),
+            date.with_time(end).assume_utc(),
+        )
+    };
+    let earliest = now + MASTODON_MIN_SCHEDULE_DELAY;
+    // The next window is today's until it ends
+    let (_, mut next_end) = window(earliest.date());
+    if earliest >= next_end {
+        next_end = window(earliest.date().next_day()?).1;
+    }
+
+    let step = (end - start) / max_per_day;
+    let at = previous
+        .map(|previous| previous + step)
+        .filter(|at| *at > earliest)
+        .unwrap_or(earliest);
+    let (window_start, window_end) = window(at.date());
+    let at = if at < window_start {
+        window_start
+    } else if at >= window_end {
+        window(at.date().next_day()?).0
+    } else {
+        at
+    };
+    Some(at).filter(|at| *at < next_end)
+}
+
+/// Returns the next time to schedule a status at on the instance, to be recorded with
+/// `record_mastodon_schedule` once it is scheduled.
+fn next_mastodon_schedule(
+    spread: &ScheduleSpreadConfig,
+    name: &str,
+) -> Result<Option<OffsetDateTime>> {
+    let previous = MASTODON_SCHEDULES.lock().unwrap().get(name).copied();
+    Ok(next_scheduled_at(
+        spread.start()?,
+        spread.end()?,
+        spread.max_per_day,
+        previous,
+        OffsetDateTime::now_utc(),
+    ))
+}
+
+fn record_mastodon_schedule(name: &str, at: OffsetDateTime) {
+    MASTODON_SCHEDULES
+        .lock()
+        .unwrap()
+        .insert(name.to_string(), at);
+}
+
+/// Posts the toot, replying to `in_reply_to_id` if any, and returns its ID.
+/// With `scheduled_at`, the toot is scheduled instead and the ID is of the scheduled status.
+async fn toot(
+    client: &reqwest::Client,
+    config: &MastodonConfig,
+    repo: &Repo,
+    content: &str,
+    in_reply_to_id: Option<&str>,
+    scheduled_at: Option<OffsetDateTime>,
+) -> Result<String> {
+    let mut media_id = None;
+    if config.attach_image {
+        match upload_mastodon_media(client, config, repo).await {
+            Ok(id) => media_id = Some(id),
+            // Fall back to the link card rather than not posting at all
+            Err(error) => warn!(
+                platform = "mastodon",
+                error = %format!("{:#}", error),
+                "failed to upload image"
+            ),
+        }
+    }
+
+    let idempotency_key =
+        make_idempotency_key(&config.instance_url, repo, OffsetDateTime::now_utc().date());
+    create_status(
+        client,
+        config,
+        &PostStatusesBody {
+            status: content,
+            visibility: "unlisted",
+            media_id: media_id.as_deref(),
+            spoiler_text: config.content_warning.as_deref(),
+            in_reply_to_id,
+            scheduled_at: scheduled_at.map(|at| at.format(&Rfc3339)).transpose()?,
+        },
+        Some(idempotency_key),
+    )
+    .await
+}
+
+/// Finds the status to reply to in the thread of the loop, posting `thread_header` to start the
+/// thread if there is none yet.
+async fn mastodon_thread_parent(
+    client: &reqwest::Client,
+    config: &MastodonConfig,
+    threads: &RefCell<HashMap<String, String>>,
+    name: &str,
+) -> Result<Option<String>> {
+    if let Some(id) = threads.borrow().get(name) {
+        return Ok(Some(id.clone()));
+    }
+    let Some(header) = &config.thread_header else {
+        return Ok(None);
+    };
+    let body = PostStatusesBody {
+        status: header,
+        visibility: "unlisted",
+        media_id: None,
+        spoiler_text: None,
+        in_reply_to_id: None,
+        scheduled_at: None,
+    };
+    let id = create_status(client, config, &body, None)
+        .await
+        .context("While posting thread header")?;
+    threads.borrow_mut().insert(name.to_string(), id.clone());
+    Ok(Some(id))
+}
+
+pub fn make_bluesky_post(
+    repo: &Repo,
+    template: &str,
+    hashtags: &str,
+    format: &FormatConfig,
+) -> String {
+    // Bluesky counts the length of posts in graphemes
+    render_post(
+        template,
+        repo,
+        format,
+        BLUESKY_POST_LENGTH,
+        LengthUnit::Graphemes,
+        None,
+        hashtags,
+        false,
+    )
+}
+
+/// Makes a link facet covering the last occurrence of `uri` in `text`.
+/// Facet indices are UTF-8 byte offsets into the text.
+fn make_bluesky_link_facet(text: &str, uri: &str) -> Option<bsky::richtext::facet::Main> {
+    let byte_start = text.rfind(uri)?;
+    let byte_end = byte_start + uri.len();
+    Some(bsky::richtext::facet::Main {
+        features: vec![bsky::richtext::facet::MainFeaturesItem::Link(Box::new(
+            bsky::richtext::facet::Link {
+                uri: uri.to_string(),
+            },
+        ))],
+        index: bsky::richtext::facet::ByteSlice {
+            byte_end: byte_end.try_into().ok()?,
+            byte_start: byte_start.try_into().ok()?,
+        },
+    })
+}
+
+/// XRPC client on `HTTP_CLIENT`, unlike `ReqwestClient` of atrium, so that Bluesky requests
+/// follow `http_timeout_secs` and `proxy` too.
+struct BlueskyClient {
+    client: reqwest::Client,
+    host: String,
+    /// Sent for requests not made by the agent, which authorizes them itself.
+    access_jwt: Option<String>,
+}
+
+impl BlueskyClient {
+    fn new(host: String) -> Self {
+        Self {
+            client: HTTP_CLIENT.clone(),
+            host,
+            access_jwt: None,
+        }
+    }
+}
+
+#[async_trait]
+impl atrium_xrpc::HttpClient for BlueskyClient {
+    async fn send(
+        &self,
+        request: hyper::http::Request<Vec<u8>>,
+    ) -> std::result::Result<
+        hyper::http::Response<Vec<u8>>,
+        Box<dyn std::error::Error + Send + Sync + 'static>,
+    > {
+        let response = self.client.execute(request.try_into()?).await?;
+        let mut builder = hyper::http::Response::builder().status(response.status());
+        for (name, value) in response.headers() {
+            builder = builder.header(name, value);
+        }
+        Ok(builder.body(response.bytes().await?.to_vec())?)
+    }
+}
+
+impl atrium_xrpc::XrpcClient for BlueskyClient {
+    fn host(&self) -> &str {
+        &self.host
+    }
+
+    fn auth(&self, _is_refresh: bool) -> Option<String> {
+        self.access_jwt.clone()
+    }
+}
+
+type BlueskyAgent = atrium_api::agent::AtpAgent<atrium_api::agent::BaseClient<BlueskyClient>>;
+
+/// Logged-in Bluesky agent, kept across posts so that it does not log in every time.
+struct BlueskySession {
+    agent: BlueskyAgent,
+    did: String,
+    /// Authorized client for the records atrium has no types of.
+    xrpc: BlueskyClient,
+}
+
+impl BlueskySession {
+    /// Restores the session saved in `session_path` if any, or logs in.
+    async fn new(config: &BlueskyConfig) -> Result<Self> {
+        let session = match config.session_path.as_deref().map(load_bluesky_session) {
+            // A saved session without a DID can't post, so it's replaced as if it's missing
+            Some(Ok(session)) if !session.did.is_empty() => session,
+            _ => create_bluesky_session(config).await?,
+        };
+        let did = session_did(&session)?;
+        let mut xrpc = BlueskyClient::new(config.host.clone());
+        xrpc.access_jwt = Some(session.access_jwt.clone());
+        let mut agent = atrium_api::agent::AtpAgent::new(BlueskyClient::new(config.host.clone()));
+        agent.set_session(session);
+        Ok(Self { agent, did, xrpc })
+    }
+
+    async fn relogin(&mut self, config: &BlueskyConfig) -> Result<()> {
+        let session = create_bluesky_session(config).await?;
+        self.did = session_did(&session)?;
+        self.xrpc.access_jwt = Some(session.access_jwt.clone());
+        self.agent.set_session(session);
+        Ok(())
+    }
+
+    /// Creates the record given as the JSON input of `com.atproto.repo.createRecord`, for records
+    /// atrium has no types of, like threadgates and posts with self-labels.
+    async fn create_record(
+        &self,
+        input: &serde_json::Value,
+    ) -> std::result::Result<
+        atproto::repo::create_record::Output,
+        atrium_xrpc::error::Error<atproto::repo::create_record::Error>,
+    > {
+        let output = atrium_xrpc::XrpcClient::send::<(), _, _, _>(
+            &self.xrpc,
+            hyper::http::Method::POST,
+            "com.atproto.repo.createRecord",
+            None,
+            Some(atrium_xrpc::InputDataOrBytes::Data(input)),
+            Some("application/json".to_string()),
+        )
+        .await?;
+        match output {
+            atrium_xrpc::OutputDataOrBytes::Data(output) => Ok(output),
+            atrium_xrpc::OutputDataOrBytes::Bytes(_) => {
+                Err(atrium_xrpc::error::Error::UnexpectedResponseType)
+            }
+        }
+    }
+}
+
+/// Values of self-labels which Bluesky shows warnings for.
+const BLUESKY_SELF_LABELS: &[&str] = &[
+    "!no-unauthenticated",
+    "porn",
+    "sexual",
+    "nudity",
+    "graphic-media",
+];
+
+/// Makes the input of `com.atproto.repo.createRecord` for the post, with the self-labels of the
+/// config if any.
+fn make_bluesky_post_input(
+    config: &BlueskyConfig,
+    did: &str,
+    record: bsky::feed::post::Record,
+) -> Result<serde_json::Value> {
+    let mut input = serde_json::to_value(atproto::repo::create_record::Input {
+        collection: "app.bsky.feed.post".to_string(),
+        record: atrium_api::records::Record::AppBskyFeedPost(Box::new(record)),
+        repo: did.to_string(),
+        rkey: None,
+        swap_commit: None,
+        validate: None,
+    })?;
+    if !config.labels.is_empty() {
+        input["record"]["labels"] = serde_json::json!({
+            "$type": "com.atproto.label.defs#selfLabels",
+            "values": config
+                .labels
+                .iter()
+                .map(|label| serde_json::json!({ "val": label }))
+                .collect::<Vec<_>>(),
+        });
+    }
+    Ok(input)
+}
+
+/// Makes the input of `com.atproto.repo.createRecord` for a threadgate allowing nobody to reply to
+/// the post. Threadgates must have the same record key as their post.
+fn make_bluesky_threadgate_input(did: &str, post_uri: &str, created_at: &str) -> serde_json::Value {
+    let rkey = post_uri.rsplit('/').next().unwrap_or_default();
+    serde_json::json!({
+        "collection": "app.bsky.feed.threadgate",
+        "repo": did,
+        "rkey": rkey,
+        "record": {
+            "$type": "app.bsky.feed.threadgate",
+            "post": post_uri,
+            "allow": [],
+            "createdAt": created_at,
+        },
+    })
+}
+
+fn session_did(session: &atrium_api::agent::Session) -> Result<String> {
+    Some(session.did.clone())
+        .filter(|did| !did.is_empty())
+        .context("missing DID after login")
+}
+
+async fn create_bluesky_session(config: &BlueskyConfig) -> Result<atrium_api::agent::Session> {
+    let client = AtpServiceClient::new(Arc::new(BlueskyClient::new(config.host.clone())));
+    let session = client
+        .com
+        .atproto
+        .server
+        .create_session(atproto::server::create_session::Input {
+            identifier: config.identifier.clone(),
+            password: config.password.clone(),
+        })
+        .await
+        .map_err(|error| match bluesky_login_hint(&error) {
+            Some(hint) => anyhow::Error::new(error).context(hint),
+            None => error.into(),
+        })?;
+
+    if let Some(path) = &config.session_path {
+        if let Err(error) =
+            save_bluesky_session(path, &session).context("While saving Bluesky session")
+        {
+            error!("{:#?}", error);
+        }
+    }
+
+    Ok(session)
+}
+
+fn load_bluesky_session(path: &Path) -> Result<atrium_api::agent::Session> {
+    let file = File::open(path)?;
+    Ok(serde_json::from_reader(BufReader::new(file))?)
+}
+
+/// Saves the session, readable only by its owner as it holds the tokens.
+fn save_bluesky_session(path: &Path, session: &atrium_api::agent::Session) -> Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let file = options.open(path)?;
+    // The mode only applies to new files, so a file saved by older versions is fixed as well
+    #[cfg(unix)]
+    file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o600))?;
+    serde_json::to_writer(file, session)?;
+    Ok(())
+}
+
+fn is_bluesky_session_expired<E>(error: &atrium_xrpc::error::Error<E>) -> bool {
+    match error {
+        atrium_xrpc::error::Error::XrpcResponse(error) => {
+            error.status.as_u16() == 401
+                || matches!(
+                    &error.error,
+                    Some(atrium_xrpc::error::XrpcErrorKind::Undefined(body))
+                        if matches!(body.error.as_deref(), Some("ExpiredToken") | Some("InvalidToken"))
+                )
+        }
+        _ => false,
+    }
+}
+
+/// Explains login errors caused by the config, which are opaque XRPC errors otherwise.
+fn bluesky_login_hint(
+    error: &atrium_xrpc::error::Error<atproto::server::create_session::Error>,
+) -> Option<&'static str> {
+    let error = match error {
+        atrium_xrpc::error::Error::XrpcResponse(error) => error,
+        _ => return None,
+    };
+    match &error.error {
+        Some(atrium_xrpc::error::XrpcErrorKind::Custom(
+            atproto::server::create_session::Error::AccountTakedown(_),
+        )) => Some("The Bluesky account has been taken down"),
+        Some(atrium_xrpc::error::XrpcErrorKind::Undefined(body)) => {
+            match body.error.as_deref() {
+                // Only asked when logging in with the account password
+                Some("AuthFactorTokenRequired") => Some(
+                    "Bluesky asked for a sign-in code sent by email, so bluesky.password is the \
+                     account password. Create an app password in Settings > Privacy and \
+                     security > App passwords and use it instead",
+                ),
+                Some("AuthenticationRequired") => Some(
+                    "Invalid bluesky.identifier or bluesky.password, which must be an app \
+                     password created in Settings > Privacy and security > App passwords",
+                ),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Makes the post with an external embed of the repo. The embed is still valid without `thumb`,
+/// which is left out when the image couldn't be fetched.
+fn make_bluesky_record(
+    config: &BlueskyConfig,
+    repo: &Repo,
+    format: &FormatConfig,
+    text: String,
+    thumb: Option<atrium_api::blob::BlobRef>,
+    created_at: String,
+) -> bsky::feed::post::Record {
+    let facets = make_bluesky_link_facet(&text, &repo_uri(repo)).map(|facet| vec![facet]);
+    bsky::feed::post::Record {
+        created_at,
+        embed: Some(bsky::feed::post::RecordEmbedEnum::AppBskyEmbedExternalMain(
+            Box::new(bsky::embed::external::Main {
+                external: bsky::embed::external::External {
+                    description: make_post_description(
+                        repo,
+                        format,
+                        BLUESKY_POST_LENGTH,
+                        LengthUnit::Graphemes,
+                    ),
+                    thumb,
+                    title: format!("{} / {}", repo.author, repo.name),
+                    uri: repo_uri(repo),
+                },
+            }),
+        )),
+        entities: None,
+        facets,
+        langs: Some(config.langs.clone()).filter(|langs| !langs.is_empty()),
+        reply: None,
+        text,
+    }
+}
+
+async fn post_bluesky(
+    config: &BlueskyConfig,
+    repo: &Repo,
+    hashtags: &str,
+    format: &FormatConfig,
+) -> Result<()> {
+    let thumbnail = if !config.embed_image {
+        None
+    } else {
+        // Post without the image rather than not posting at all
+        match get_github_og_image(repo).await {
+            Ok(thumbnail) => Some(thumbnail),
+            Err(error) => {
+                warn!(
+                    platform = "bluesky",
+                    repo = %repo_slug(repo),
+                    error = %format!("{:#}", error),
+                    "failed to fetch image, posting without it"
+                );
+                None
+            }
+        }
+    };
+
+    let text = make_bluesky_post(repo, config.template(), hashtags, format);
+
+    static SESSION: Lazy<tokio::sync::Mutex<Option<BlueskySession>>> = Lazy::new(Default::default);
+    let mut session_guard = SESSION.lock().await;
+    let session = match session_guard.take() {
+        Some(session) => session,
+        None => BlueskySession::new(config).await?,
+    };
+    let session = session_guard.insert(session);
+
+    let blob = match thumbnail {
+        Some(thumbnail) => Some(
+            match session
+                .agent
+                .api
+                .com
+                .atproto
+                .repo
+                .upload_blob(thumbnail.to_vec())
+                .await
+            {
+                Err(error) if is_bluesky_session_expired(&error) => {
+                    info!(platform = "bluesky", "session expired, logging in again");
+                    session.relogin(config).await?;
+                    session
+                        .agent
+                        .api
+                        .com
+                        .atproto
+                        .repo
+                        .upload_blob(thumbnail.to_vec())
+                        .await?
+                }
+                result => result?,
+            }
+            .blob,
+        ),
+        None => None,
+    };
+
+    let created_at = OffsetDateTime::now_utc().format(&Rfc3339)?;
+    let record = make_bluesky_record(config, repo, format, text, blob, created_at.clone());
+    let input = make_bluesky_post_input(config, &session.did, record.clone())?;
+    // Without an image, the expired session is only found here
+    let post = match session.create_record(&input).await {
+        Err(error) if is_bluesky_session_expired(&error) => {
+            info!(platform = "bluesky", "session expired, logging in again");
+            session.relogin(config).await?;
+            let input = make_bluesky_post_input(config, &session.did, record)?;
+            session.create_record(&input).await?
+        }
+        result => result?,
+    };
+
+    if config.disable_replies {
+        // The post is already created, so failing here would only post it again
+        let input = make_bluesky_threadgate_input(&session.did, &post.uri, &created_at);
+        if let Err(error) = session.create_record(&input).await {
+            warn!(
+                platform = "bluesky",
+                repo = %repo_slug(repo),
+                error = %error,
+                "failed to disable replies"
+            );
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Serialize, Debug)]
+struct DiscordMessageBody<'a> {
+    embeds: [DiscordEmbed<'a>; 1],
+}
+
+#[derive(Serialize, Debug)]
+struct DiscordEmbed<'a> {
+    title: String,
+    url: String,
+    description: String,
+    fields: [DiscordEmbedField<'a>; 1],
+}
+
+#[derive(Serialize, Debug)]
+struct DiscordEmbedField<'a> {
+    name: &'a str,
+    value: String,
+    inline: bool,
+}
+
+fn make_discord_body(repo: &Repo, format: &FormatConfig) -> DiscordMessageBody<'static> {
+    DiscordMessageBody {
+        embeds: [DiscordEmbed {
+            title: make_repo_title(repo),
+            url: repo_uri(repo),
+            description: make_post_description(
+                repo,
+                format,
+                DISCORD_POST_LENGTH,
+                LengthUnit::Graphemes,
+            ),
+            fields: [DiscordEmbedField {
+                name: "Stars",
+                value: format.stars(repo.stars),
+                inline: true,
+            }],
+        }],
+    }
+}
+
+async fn post_discord(
+    client: &reqwest::Client,
+    config: &DiscordConfig,
+    repo: &Repo,
+    format: &FormatConfig,
+) -> Result<()> {
+    let request = client
+        .post(config.webhook_url.clone())
+        .json(&make_discord_body(repo, format));
+    send_with_rate_limit(request).await?;
+    Ok(())
+}
+
+/// Creates the same message as the webhook, with the bot token.
+async fn post_discord_bot(
+    client: &reqwest::Client,
+    config: &DiscordBotConfig,
+    repo: &Repo,
+    format: &FormatConfig,
+) -> Result<()> {
+    let request = client
+        .post(format!(
+            "{}/channels/{}/messages",
+            DISCORD_API_URL, config.channel_id
+        ))
+        .header(
+            reqwest::header::AUTHORIZATION,
+            format!("Bot {}", config.token),
+        )
+        .json(&make_discord_body(repo, format));
+    send_with_rate_limit(request).await?;
+    Ok(())
+}
+
+#[derive(Serialize, Debug)]
+struct TelegramSendMessageBody<'a> {
+    chat_id: &'a str,
+    text: &'a str,
+    parse_mode: &'a str,
+}
+
+/// The bot token is a part of the URL, which is left out of errors by `send_with_rate_limit`.
+async fn post_telegram(
+    client: &reqwest::Client,
+    api_url: &str,
+    config: &TelegramConfig,
+    content: &str,
+) -> Result<()> {
+    let url = format!("{}/bot{}/sendMessage", api_url, config.bot_token);
+    let request = client.post(url).form(&TelegramSendMessageBody {
+        chat_id: &config.chat_id,
+        text: content,
+        parse_mode: "HTML",
+    });
+    send_with_rate_limit(request).await?;
+    Ok(())
+}
+
+/// Block Kit message with the linked title and description, and the stars as context.
+fn make_slack_body(repo: &Repo, format: &FormatConfig) -> serde_json::Value {
+    let title = escape_html(&make_repo_title(repo));
+    let stars = format.stars(repo.stars);
+    serde_json::json!({
+        "text": format!("{}: {} {}", title, escape_html(&repo.description), stars),
+        "blocks": [
+            {
+                "type": "section",
+                "text": {
+                    "type": "mrkdwn",
+                    "text": format!(
+                        "*<{}|{}>*\n{}",
+                        repo_uri(repo),
+                        title,
+                        escape_html(&repo.description)
+                    ),
+                },
+            },
+            {
+                "type": "context",
+                "elements": [{ "type": "mrkdwn", "text": stars }],
+            },
+        ],
+    })
+}
+
+async fn post_slack(
+    client: &reqwest::Client,
+    config: &SlackConfig,
+    repo: &Repo,
+    format: &FormatConfig,
+) -> Result<()> {
+    let request = client
+        .post(config.webhook_url.clone())
+        .json(&make_slack_body(repo, format));
+    send_with_rate_limit(request).await?;
+    Ok(())
+}
+
+#[derive(Serialize, Debug)]
+struct MatrixMessageBody {
+    msgtype: &'static str,
+    body: String,
+    format: &'static str,
+    formatted_body: String,
+}
+
+fn make_matrix_message(repo: &Repo, format: &FormatConfig) -> MatrixMessageBody {
+    MatrixMessageBody {
+        msgtype: "m.text",
+        body: expand_template(DEFAULT_TEMPLATE, |placeholder| {
+            template_value(repo, format, placeholder)
+        }),
+        format: "org.matrix.custom.html",
+        formatted_body: format!(
+            "<a href=\"{}\">{}</a>: {}{}",
+            repo_uri(repo),
+            escape_html(&make_repo_title(repo)),
+            escape_html(&repo.description),
+            make_post_stars(repo, format)
+        ),
+    }
+}
+
+fn matrix_send_url(config: &MatrixConfig, txn_id: &str) -> Result<Url> {
+    let mut url = config.homeserver.clone();
+    url.path_segments_mut()
+        .map_err(|_| anyhow::anyhow!("Matrix homeserver can't be a base URL"))?
+        .pop_if_empty()
+        .extend(&["_matrix", "client", "v3", "rooms"])
+        .push(&config.room_id)
+        .extend(&["send", "m.room.message", txn_id]);
+    Ok(url)
+}
+
+async fn post_matrix(
+    client: &reqwest::Client,
+    config: &MatrixConfig,
+    repo: &Repo,
+    format: &FormatConfig,
+) -> Result<()> {
+    // Retries of this request reuse the transaction ID, so the homeserver sends the message once
+    let txn_id = random_string::generate(32, "0123456789abcdefghijklmnopqrstuvwxyz");
+    let request = client
+        .put(matrix_send_url(config, &txn_id)?)
+        .bearer_auth(&config.access_token)
+        .json(&make_matrix_message(repo, format));
+    send_with_rate_limit(request).await?;
+    Ok(())
+}
+
+pub fn make_webhook_text(repo: &Repo, template: &str, format: &FormatConfig) -> String {
+    expand_template(template, |placeholder| {
+        template_value(repo, format, placeholder)
+    })
+}
+
+#[derive(Serialize, Debug)]
+struct WebhookBody<'a> {
+    repo: &'a Repo,
+    text: &'a str,
+}
+
+async fn post_webhook(
+    client: &reqwest::Client,
+    config: &WebhookConfig,
+    repo: &Repo,
+    content: &str,
+) -> Result<()> {
+    let mut request = client.post(config.url.clone());
+    for (name, value) in &config.headers {
+        request = request.header(name, value);
+    }
+    let request = request.json(&WebhookBody {
+        repo,
+        text: content,
+    });
+    send_with_rate_limit(request).await?;
+    Ok(())
+}
+
+/// Nostr event of NIP-01.
+#[derive(Serialize, Debug)]
+struct NostrEvent {
+    id: String,
+    pubkey: String,
+    created_at: u64,
+    kind: u32,
+    tags: Vec<Vec<String>>,
+    content: String,
+    sig: String,
+}
+
+pub fn make_nostr_post(
+    repo: &Repo,
+    template: &str,
+    hashtags: &str,
+    format: &FormatConfig,
+) -> String {
+    render_post(
+        template,
+        repo,
+        format,
+        NOSTR_POST_LENGTH,
+        LengthUnit::Chars,
+        None,
+        hashtags,
+        false,
+    )
+}
+
+/// Signs the id of an event with BIP-340. The auxiliary randomness is all zeros, which BIP-340
+/// allows, so that a signature can be checked against the test vectors.
+fn sign_nostr_id(keypair: &KeyPair, id: &[u8]) -> Result<secp256k1::schnorr::Signature> {
+    let message = secp256k1::Message::from_slice(id)?;
+    Ok(SECP256K1.sign_schnorr_with_aux_rand(&message, keypair, &[0; 32]))
+}
+
+/// Makes a signed text note. The id is the hash of the serialized event, which is signed.
+fn make_nostr_event(keypair: &KeyPair, content: &str, created_at: u64) -> Result<NostrEvent> {
+    let pubkey = keypair.x_only_public_key().0.to_string();
+    let kind = 1;
+    let tags = Vec::<Vec<String>>::new();
+    let serialized = serde_json::to_string(&(0, &pubkey, created_at, kind, &tags, content))?;
+    let id = Sha256::digest(serialized.as_bytes());
+    let sig = sign_nostr_id(keypair, &id)?;
+    Ok(NostrEvent {
+        id: format!("{:x}", id),
+        pubkey,
+        created_at,
+        kind,
+        tags,
+        content: content.to_string(),
+        sig: sig.to_string(),
+    })
+}
+
+/// Publishes the event to the relay and waits for the relay to accept it, within
+/// `http_timeout_secs` from connecting.
+async fn publish_nostr_event(relay: &Url, event: &NostrEvent) -> Result<()> {
+    use futures::{SinkExt, StreamExt};
+    use tokio_tungstenite::tungstenite::Message;
+
+    let response = tokio::time::timeout(http_timeout(), async {
+        let (mut stream, _) = tokio_tungstenite::connect_async(relay.as_str()).await?;
+        let request = serde_json::to_string(&("EVENT", event))?;
+        stream.send(Message::Text(request)).await?;
+
+        while let Some(message) = stream.next().await {
+            if let Message::Text(text) = message? {
+                // ["OK", <event id>, <accepted>, <message>]
+                let response: serde_json::Value = serde_json::from_str(&text)?;
+                if response[0] == "OK" && response[1] == event.id.as_str() {
+                    let _ = stream.close(None).await;
+                    return Ok(response);
+                }
+            }
+        }
+        bail!("Relay closed the connection")
+    })
+    .await
+    .context("Timed out publishing to the relay")??;
+
+    if response[2] != true {
+        bail!("Relay rejected the event: {}", response[3]);
+    }
+    Ok(())
+}
+
+/// Publishes to every relay, and fails only when no relay accepted the note.
+async fn post_nostr(config: &NostrConfig, content: &str) -> Result<()> {
+    let keypair = KeyPair::from_seckey_str(SECP256K1, &config.secret_key)?;
+    let event = make_nostr_event(&keypair, content, now_ts())?;
+
+    let results = join_all(
+        config
+            .relays
+            .iter()
+            .map(|relay| publish_nostr_event(relay, &event)),
+    )
+    .await;
+    let mut published = false;
+    for (relay, result) in config.relays.iter().zip(results) {
+        match result {
+            Ok(()) => published = true,
+            Err(error) => warn!(
+                platform = "nostr",
+                %relay,
+                error = %format!("{:#}", error),
+                "failed to publish"
+            ),
+        }
+    }
+    if !published {
+        bail!("No Nostr relay accepted the note");
+    }
+    Ok(())
+}
+
+#[derive(Serialize, Debug)]
+struct LemmyLoginBody<'a> {
+    username_or_email: &'a str,
+    password: &'a str,
+}
+
+#[derive(Deserialize)]
+struct LemmyLoginResponse {
+    /// Missing when the account is waiting for an email verification or an approval.
+    jwt: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct LemmyCommunityResponse {
+    community_view: LemmyCommunityView,
+}
+
+#[derive(Deserialize)]
+struct LemmyCommunityView {
+    community: LemmyCommunity,
+}
+
+#[derive(Deserialize)]
+struct LemmyCommunity {
+    id: u64,
+}
+
+#[derive(Serialize, Debug)]
+struct LemmyCreatePostBody {
+    name: String,
+    community_id: u64,
+    url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    body: Option<String>,
+}
+
+/// Link post to the repo, with the description as its body.
+fn make_lemmy_post(repo: &Repo, community_id: u64) -> LemmyCreatePostBody {
+    LemmyCreatePostBody {
+        name: make_repo_title(repo),
+        community_id,
+        url: repo_uri(repo),
+        body: Some(repo.description.clone()).filter(|description| !description.is_empty()),
+    }
+}
+
+/// Logged in Lemmy account, kept across posts so that it doesn't log in for every repo.
+struct LemmySession {
+    jwt: String,
+    community_id: u64,
+}
+
+impl LemmySession {
+    async fn login(client: &reqwest::Client, config: &LemmyConfig) -> Result<Self> {
+        let url = config.instance_url.join("./api/v3/user/login")?;
+        let response: LemmyLoginResponse =
+            send_with_rate_limit(client.post(url).json(&LemmyLoginBody {
+                username_or_email: &config.username,
+                password: &config.password,
+            }))
+            .await?
+            .json()
+            .await?;
+        let jwt = response
+            .jwt
+            .context("Lemmy returned no token, the account may not be verified or approved yet")?;
+
+        let url = config.instance_url.join("./api/v3/community")?;
+        let community: LemmyCommunityResponse = send_with_rate_limit(
+            client
+                .get(url)
+                .query(&[("name", &config.community)])
+                .bearer_auth(&jwt),
+        )
+        .await?
+        .json()
+        .await
+        .with_context(|| format!("While resolving Lemmy community {}", config.community))?;
+
+        Ok(Self {
+            jwt,
+            community_id: community.community_view.community.id,
+        })
+    }
+}
+
+fn is_unauthorized(error: &anyhow::Error) -> bool {
+    error
+        .downcast_ref::<reqwest::Error>()
+        .and_then(|error| error.status())
+        == Some(reqwest::StatusCode::UNAUTHORIZED)
+}
+
+async fn post_lemmy(client: &reqwest::Client, config: &LemmyConfig, repo: &Repo) -> Result<()> {
+    static SESSION: Lazy<tokio::sync::Mutex<Option<LemmySession>>> = Lazy::new(Default::default);
+
+    let mut session_guard = SESSION.lock().await;
+    let session = match session_guard.take() {
+        Some(session) => session,
+        None => LemmySession::login(client, config).await?,
+    };
+    let session = session_guard.insert(session);
+
+    let url = config.instance_url.join("./api/v3/post")?;
+    let request = |session: &LemmySession| {
+        client
+            .post(url.clone())
+            .bearer_auth(&session.jwt)
+            .json(&make_lemmy_post(repo, session.community_id))
+    };
+    match send_with_rate_limit(request(session)).await {
+        Err(error) if is_unauthorized(&error) => {
+            info!(platform = "lemmy", "session expired, logging in again");
+            *session = LemmySession::login(client, config).await?;
+            send_with_rate_limit(request(session)).await?;
+        }
+        result => {
+            result?;
+        }
+    }
+    Ok(())
+}
+
+#[derive(Serialize, Debug)]
+struct RedditTokenBody<'a> {
+    grant_type: &'a str,
+    username: &'a str,
+    password: &'a str,
+}
+
+#[derive(Deserialize)]
+struct RedditTokenResponse {
+    access_token: Option<String>,
+    expires_in: Option<u64>,
+    /// Reddit responds wrong credentials with 200 and this, like `invalid_grant`.
+    error: Option<String>,
+}
+
+/// OAuth token of the script app, kept until it expires.
+struct RedditToken {
+    access_token: String,
+    expires_at: Instant,
+}
+
+impl RedditToken {
+    async fn fetch(client: &reqwest::Client, config: &RedditConfig) -> Result<Self> {
+        let response: RedditTokenResponse = send_with_rate_limit(
+            client
+                .post(REDDIT_TOKEN_URL)
+                .basic_auth(&config.client_id, Some(&config.client_secret))
+                .form(&RedditTokenBody {
+                    grant_type: "password",
+                    username: &config.username,
+                    password: &config.password,
+                }),
+        )
+        .await?
+        .json()
+        .await?;
+        let access_token = match response.access_token {
+            Some(access_token) => access_token,
+            None => bail!(
+                "Reddit returned no token: {}",
+                response.error.as_deref().unwrap_or("unknown error")
+            ),
+        };
+        Ok(Self {
+            access_token,
+            expires_at: Instant::now() + Duration::from_secs(response.expires_in.unwrap_or(3600)),
+        })
+    }
+
+    fn is_expired(&self, now: Instant) -> bool {
+        now + REDDIT_TOKEN_EXPIRY_MARGIN >= self.expires_at
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct RedditSubmitBody<'a> {
+    api_type: &'a str,
+    kind: &'a str,
+    sr: &'a str,
+    title: String,
+    url: String,
+    /// Reposts on star growth would be rejected as already submitted otherwise.
+    resubmit: bool,
+}
+
+fn make_reddit_submission<'a>(config: &'a RedditConfig, repo: &Repo) -> RedditSubmitBody<'a> {
+    RedditSubmitBody {
+        api_type: "json",
+        kind: "link",
+        sr: &config.subreddit,
+        title: make_repo_title(repo),
+        url: repo_uri(repo),
+        resubmit: true,
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct RedditSubmitResponse {
+    json: RedditSubmitResult,
+}
+
+#[derive(Deserialize, Debug)]
+struct RedditSubmitResult {
+    /// Errors like `["RATELIMIT", "Take a break for 9 minutes", "ratelimit"]`, which Reddit
+    /// responds with 200.
+    #[serde(default)]
+    errors: Vec<Vec<serde_json::Value>>,
+}
+
+impl RedditSubmitResponse {
+    fn into_result(self) -> Result<()> {
+        if self.json.errors.is_empty() {
+            return Ok(());
+        }
+        let errors = self
+            .json
+            .errors
+            .iter()
+            .map(|error| {
+                error
+                    .iter()
+                    .filter_map(|value| value.as_str())
+                    .collect::<Vec<_>>()
+                    .join(": ")
+            })
+            .collect::<Vec<_>>();
+        bail!("Reddit rejected the post: {}", errors.join(", "))
+    }
+}
+
+async fn post_reddit(client: &reqwest::Client, config: &RedditConfig, repo: &Repo) -> Result<()> {
+    static TOKEN: Lazy<tokio::sync::Mutex<Option<RedditToken>>> = Lazy::new(Default::default);
+
+    let mut token_guard = TOKEN.lock().await;
+    let token = match token_guard.take() {
+        Some(token) if !token.is_expired(Instant::now()) => token,
+        _ => RedditToken::fetch(client, config).await?,
+    };
+    let token = token_guard.insert(token);
+
+    let request = |token: &RedditToken| {
+        client
+            .post(REDDIT_SUBMIT_URL)
+            .bearer_auth(&token.access_token)
+            .form(&make_reddit_submission(config, repo))
+    };
+    let response = match send_with_rate_limit(request(token)).await {
+        Err(error) if is_unauthorized(&error) => {
+            info!(platform = "reddit", "token revoked, fetching a new one");
+            *token = RedditToken::fetch(client, config).await?;
+            send_with_rate_limit(request(token)).await?
+        }
+        result => result?,
+    };
+    response.json::<RedditSubmitResponse>().await?.into_result()
+}
+
+fn make_feed_entry(repo: &Repo, updated: FixedDateTime) -> atom_syndication::Entry {
+    let uri = repo_uri(repo);
+    atom_syndication::Entry {
+        title: make_repo_title(repo).into(),
+        id: uri.clone(),
+        updated,
+        links: vec![atom_syndication::Link {
+            href: uri,
+            ..Default::default()
+        }],
+        summary: Some(repo.description.as_str().into()),
+        ..Default::default()
+    }
+}
+
+fn append_feed(config: &FeedConfig, repo: &Repo) -> Result<()> {
+    let mut feed = match File::open(&config.output_path) {
+        Ok(file) => atom_syndication::Feed::read_from(BufReader::new(file))?,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => atom_syndication::Feed {
+            title: "Rust Trending".into(),
+            id: "https://github.com/pbzweihander/rust-trending".to_string(),
+            ..Default::default()
+        },
+        Err(error) => return Err(error.into()),
+    };
+
+    let updated = FixedDateTime::parse_from_rfc3339(
+        &OffsetDateTime::from_unix_timestamp(now_ts() as i64)?.format(&Rfc3339)?,
+    )?;
+    feed.updated = updated;
+    feed.entries.insert(0, make_feed_entry(repo, updated));
+    feed.entries.truncate(config.max_items);
+
+    // Write to a temporary file first so readers never see a partially written feed
+    let tmp_path = config.output_path.with_extension("tmp");
+    feed.write_to(File::create(&tmp_path)?)?;
+    std::fs::rename(&tmp_path, &config.output_path)?;
+    Ok(())
+}
+
+async fn mark_posted_key(
+    store: &mut dyn Store,
+    key: &str,
+    repo: &Repo,
+    platforms: Vec<String>,
+    ttl: usize,
+) -> Result<()> {
+    let record = PostedRecord {
+        posted_at: now_ts(),
+        stars_at_post: repo.stars,
+        platforms,
+    };
+    store
+        .mark_posted(key, &serde_json::to_string(&record)?, ttl)
+        .await
+}
+
+async fn mark_posted_repo(
+    store: &mut dyn Store,
+    prefix: &str,
+    repo: &Repo,
+    platforms: Vec<String>,
+    ttl: usize,
+) -> Result<()> {
+    mark_posted_key(store, &repo_key(prefix, repo), repo, platforms, ttl).await
+}
+
+/// Number of requests a single post is given time for.
+const POST_TIMEOUT_REQUESTS: u32 = 4;
+
+/// Deadline of a single post, which also waits out the rate limits of `send_with_rate_limit`, so
+/// that a post isn't timed out while waiting for the delay asked by the platform.
+fn post_timeout() -> Duration {
+    http_timeout() * POST_TIMEOUT_REQUESTS + RATE_LIMIT_MAX_DELAY * RATE_LIMIT_RETRIES
+}
+
+/// Counts of repos seen in a single loop.
+#[derive(Default, Debug)]
+struct LoopSummary {
+    fetched: usize,
+    /// Skipped by `min_stars`, `min_stars_period`, `skip_empty_description`,
+    /// `allowed_description_langs` or `max_per_author_per_run`.
+    filtered: usize,
+    denylisted: usize,
+    already_posted: usize,
+    /// Number of repos newly posted to each platform.
+    posted: BTreeMap<&'static str, usize>,
+    /// Number of repos posted to every platform.
+    repos_posted: usize,
+    /// Number of failed posts across platforms.
+    errors: usize,
+    /// Platform names and errors of the retryable failed posts not deadlettered yet.
+    failures: Vec<(String, String)>,
+    /// Number of repos posted of each lowercased author.
+    authors_posted: HashMap<String, usize>,
+    /// Last status in the thread of each Mastodon instance with `thread`.
+    mastodon_threads: RefCell<HashMap<String, String>>,
+    /// Whether posting was paused in the middle of the loop.
+    paused: bool,
+}
+
+impl LoopSummary {
+    fn reached_max_posts(&self, config: &TrendingConfig) -> bool {
+        config
+            .max_posts_per_run
+            .is_some_and(|max_posts| self.repos_posted >= max_posts)
+    }
+
+    fn reached_max_per_author(&self, config: &TrendingConfig, author: &str) -> bool {
+        config.max_per_author_per_run.is_some_and(|max_posts| {
+            self.authors_posted
+                .get(&author.to_lowercase())
+                .is_some_and(|posted| *posted >= max_posts)
+        })
+    }
+
+    fn log(&self) {
+        let posted = self
+            .posted
+            .iter()
+            .map(|(platform, count)| format!("{}={}", platform, count))
+            .collect::<Vec<_>>()
+            .join(",");
+        info!(
+            fetched = self.fetched,
+            filtered = self.filtered,
+            denylisted = self.denylisted,
+            already_posted = self.already_posted,
+            posted = %posted,
+            repos_posted = self.repos_posted,
+            errors = self.errors,
+            "loop finished"
+        );
+    }
+
+    fn observe(&self) {
+        for (outcome, count) in [
+            ("fetched", self.fetched),
+            ("filtered", self.filtered),
+            ("denylisted", self.denylisted),
+            ("already_posted", self.already_posted),
+            ("errors", self.errors),
+        ] {
+            LAST_LOOP_REPOS
+                .with_label_values(&[outcome])
+                .set(count as i64);
+        }
+    }
+}
+
+/// Alerts of the same kind are sent at most once in this duration, so that an outage doesn't
+/// flood the alert webhook.
+const ALERT_THROTTLE: Duration = Duration::from_secs(10 * 60);
+/// Platforms are alerted after failing this many posts in a row.
+const ALERT_POST_FAILURES: u32 = 3;
+
+#[derive(Serialize, Debug)]
+struct AlertBody<'a> {
+    level: &'a str,
+    message: &'a str,
+}
+
+/// State of `alert_webhook` across loops.
+#[derive(Default)]
+struct Alerts {
+    /// When an alert of each kind was last sent.
+    sent_at: HashMap<String, Instant>,
+    /// Number of posts failed in a row of each platform.
+    post_failures: HashMap<String, u32>,
+}
+
+impl Alerts {
+    /// Whether an alert of the kind can be sent now, which is then counted as sent.
+    fn should_send(&mut self, kind: &str, now: Instant) -> bool {
+        match self.sent_at.get(kind) {
+            Some(sent_at) if now.duration_since(*sent_at) < ALERT_THROTTLE => false,
+            _ => {
+                self.sent_at.insert(kind.to_string(), now);
+                true
+            }
+        }
+    }
+
+    /// Counts a post to the platform, returning the number of posts failed in a row.
+    fn record_post(&mut self, platform: &str, succeeded: bool) -> u32 {
+        if succeeded {
+            self.post_failures.remove(platform);
+            return 0;
+        }
+        let failures = self.post_failures.entry(platform.to_string()).or_default();
+        *failures += 1;
+        *failures
+    }
+}
+
+static ALERTS: Lazy<std::sync::Mutex<Alerts>> = Lazy::new(Default::default);
+
+/// Message of an alert, with the status of the response if any. The error itself is only logged,
+/// as it can hold URLs and responses not meant to leave the host.
+fn alert_message(summary: &str, status: Option<reqwest::StatusCode>) -> String {
+    match status {
+        Some(status) => format!("{} (status {}), see the logs", summary, status),
+        None => format!("{}, see the logs", summary),
+    }
+}
+
+/// Sends an alert to `alert_webhook` unless one of the kind was sent recently. Failures are only
+/// logged, as there is nowhere else to report them.
+async fn send_alert(config: &Config, kind: &str, message: &str) {
+    let Some(url) = &config.alert_webhook else {
+        return;
+    };
+    if !ALERTS.lock().unwrap().should_send(kind, Instant::now()) {
+        debug!(kind, "skipped alert: sent recently");
+        return;
+    }
+    let request = HTTP_CLIENT.post(url.clone()).json(&AlertBody {
+        level: "error",
+        message,
+    });
+    if let Err(error) = send_with_rate_limit(request).await {
+        warn!(kind, error = %format!("{:#}", error), "failed to send alert");
+    }
+}
+
+/// Posts the digest once a day after its time. Platforms are posted concurrently and failures
+/// are only logged, as retrying would post the digest again on the platforms that succeeded.
+async fn post_digest(
+    config: &Config,
+    store: &mut dyn Store,
+    digest: &DigestConfig,
+    now: OffsetDateTime,
+) -> Result<()> {
+    let key = format!("digest:{}", now.date());
+    if now.time() < digest.time()? || store.is_posted(&key).await? {
+        return Ok(());
+    }
+
+    let language = config
+        .trending
+        .languages
+        .first()
+        .map_or("rust", String::as_str);
+    let repos = fetch_repos(&config.github, language, Since::Daily)
+        .await
+        .context("While fetching repos")?
+        .into_iter()
+        .filter(|repo| !config.denylist.contains(repo))
+        .collect::<Vec<_>>();
+    if repos.is_empty() {
+        return Ok(());
+    }
+    // Platforms whose denylist filters every repo are skipped, rather than posting an empty digest
+    let top = |denylist: &DenylistConfig| {
+        let repos = repos
+            .iter()
+            .filter(|repo| !denylist.contains(repo))
+            .take(digest.top_n)
+            .collect::<Vec<_>>();
+        Some(repos).filter(|repos| !repos.is_empty())
+    };
+    let format = &config.format;
+    let dry_run = config.dry_run;
+
+    let mut posts: Vec<(String, LocalBoxFuture<Result<()>>)> = Vec::new();
+    if let Some((config, top)) = config
+        .twitter
+        .as_ref()
+        .and_then(|config| Some((config, top(&config.denylist)?)))
+    {
+        let content = make_digest(language, top, format, TWEET_LENGTH, LengthUnit::Bytes, None);
+        posts.push((
+            "twitter".to_string(),
+            async move {
+                if dry_run {
+                    return log_dry_run("twitter", &content);
+                }
+                tweet(config, content).await
+            }
+            .boxed_local(),
+        ));
+    }
+    for (config, top) in config
+        .mastodon
+        .iter()
+        .filter_map(|config| Some((config, top(&config.denylist)?)))
+    {
+        let content_warning_length = config
+            .content_warning
+            .as_deref()
+            .map_or(0, |cw| cw.chars().count());
+        let content = make_digest(
+            language,
+            top,
+            format,
+            TOOT_LENGTH.saturating_sub(content_warning_length),
+            LengthUnit::Chars,
+            Some(MASTODON_FIXED_URL_LENGTH),
+        );
+        posts.push((
+            mastodon_platform_name(config),
+            async move {
+                if dry_run {
+                    return log_dry_run(config.instance_url.as_str(), &content);
+                }
+                let body = PostStatusesBody {
+                    status: &content,
+                    visibility: "unlisted",
+                    media_id: None,
+                    spoiler_text: config.content_warning.as_deref(),
+                    in_reply_to_id: None,
+                    scheduled_at: None,
+                };
+                create_status(&HTTP_CLIENT, config, &body, None).await?;
+                Ok(())
+            }
+            .boxed_local(),
+        ));
+    }
+    if let Some((config, top)) = config
+        .telegram
+        .as_ref()
+        .and_then(|config| Some((config, top(&config.denylist)?)))
+    {
+        // Escaped entities don't count toward the limit of Telegram
+        let content = escape_html(&make_digest(
+            language,
+            top,
+            format,
+            TELEGRAM_POST_LENGTH,
+            LengthUnit::Graphemes,
+            None,
+        ));
+        posts.push((
+            "telegram".to_string(),
+            async move {
+                if dry_run {
+                    return log_dry_run("telegram", &content);
+                }
+                post_telegram(&HTTP_CLIENT, TELEGRAM_API_URL, config, &content).await
+            }
+            .boxed_local(),
+        ));
+    }
+    if let Some((config, top)) = config
+        .nostr
+        .as_ref()
+        .and_then(|config| Some((config, top(&config.denylist)?)))
+    {
+        let content = make_digest(
+            language,
+            top,
+            format,
+            NOSTR_POST_LENGTH,
+            LengthUnit::Chars,
+            None,
+        );
+        posts.push((
+            "nostr".to_string(),
+            async move {
+                if dry_run {
+                    return log_dry_run("nostr", &content);
+                }
+                post_nostr(config, &content).await
+            }
+            .boxed_local(),
+        ));
+    }
+
+    let results = join_all(posts.into_iter().map(|(name, post)| async move {
+        let result = tokio::time::timeout(post_timeout(), post)
+            .await
+            .unwrap_or_else(|_| Err(anyhow!("Timed out posting to {}", name)));
+        (name, result)
+    }))
+    .await;
+    for (name, result) in results {
+        if let Err(error) = result {
+            error!(
+                platform = %name,
+                error = %format!("{:#}", error),
+                "failed to post digest"
+            );
+        }
+    }
+
+    if !dry_run {
+        store
+            .mark_posted(&key, &now.unix_timestamp().to_string(), DIGEST_TTL)
+            .await
+            .context("While marking digest posted")?;
+    }
+    Ok(())
+}
+
+/// Whether operators paused posting with `paused_key`, to be checked before posting so that they
+/// can pause during incidents without restarting.
+async fn is_paused(config: &Config, store: &mut dyn Store) -> Result<bool> {
+    let paused = store
+        .flag(&config.paused_key)
+        .await
+        .context("While checking paused")?
+        .is_some_and(|value| is_truthy(&value));
+    if paused {
+        warn!(key = %config.paused_key, "paused");
+    }
+    Ok(paused)
+}
+
+async fn main_loop(config: &Config, store: &mut dyn Store) -> Result<LoopSummary> {
+    let mut summary = LoopSummary::default();
+    if is_paused(config, store).await? {
+        return Ok(summary);
+    }
+    // Before fetching, so that failed posts are delivered even if the repos are no longer trending
+    if let Err(error) = retry_deadletters(config, store).await {
+        error!(error = %format!("{:#}", error), "failed to retry deadlettered posts");
+    }
+    if let Some(digest) = config.digest.as_ref().filter(|digest| digest.enabled) {
+        // Not to hold back the trending repos, and retried in the next loop as it is not marked
+        if let Err(error) = post_digest(config, store, digest, OffsetDateTime::now_utc()).await {
+            error!(error = %format!("{:#}", error), "failed to post digest");
+        }
+        if digest.only {
+            return Ok(summary);
+        }
+    }
+    'outer: for language in &config.trending.languages {
+        for since in &config.trending.since {
+            if summary.paused || summary.reached_max_posts(&config.trending) {
+                break 'outer;
+            }
+            post_trending(config, store, language, *since, &mut summary).await?;
+        }
+    }
+
+    if summary.reached_max_posts(&config.trending) {
+        info!(
+            max_posts_per_run = config.trending.max_posts_per_run,
+            "reached max_posts_per_run, leaving the rest to the next loop"
+        );
+    }
+    summary.log();
+    Ok(summary)
+}
+
+/// Lists as many of the repos as fit in `length`, like `1. author / name ★123 url` on each line
+/// after the header.
+pub fn make_digest<'a>(
+    language: &str,
+    repos: impl IntoIterator<Item = &'a Repo>,
+    format: &FormatConfig,
+    length: usize,
+    unit: LengthUnit,
+    fixed_url_length: Option<usize>,
+) -> String {
+    let lines = repos
+        .into_iter()
+        .enumerate()
+        .map(|(index, repo)| {
+            let text = format!(
+                "{}. {} {} ",
+                index + 1,
+                escape_mentions(&make_repo_title(repo), format),
+                format.stars(repo.stars)
+            );
+            let url = repo_uri(repo);
+            let line_length = unit.len(&text) + fixed_url_length.unwrap_or_else(|| unit.len(&url));
+            (text + &url, line_length)
+        })
+        .collect::<Vec<_>>();
+    let mut language = language.to_string();
+    if let Some(first) = language.get_mut(..1) {
+        first.make_ascii_uppercase();
+    }
+
+    let mut count = lines.len();
+    loop {
+        let header = format!("Top {} trending {} repos today:", count, language);
+        let digest_length = unit.len(&header)
+            + lines[..count]
+                .iter()
+                .map(|(_, line_length)| 1 + line_length)
+                .sum::<usize>();
+        if digest_length <= length || count <= 1 {
+            return std::iter::once(header)
+                .chain(lines.into_iter().take(count).map(|(line, _)| line))
+                .collect::<Vec<_>>()
+                .join("\n");
+        }
+        count -= 1;
+    }
+}
+
+fn log_dry_run(platform: &str, content: &str) -> Result<()> {
+    info!(platform, "dry-run: would post:\n{}", content);
+    Ok(())
+}
+
+/// Whether the repo is posted to the platform of the key. When reposting, only posts with at least
+/// `repost_threshold` stars count.
+async fn is_platform_posted(
+    store: &mut dyn Store,
+    key: &str,
+    repo: &Repo,
+    repost_threshold: Option<usize>,
+) -> Result<bool> {
+    Ok(match repost_threshold {
+        Some(threshold) => get_posted_key_record(store, key, repo)
+            .await
+            .context("While reading posted repo")?
+            .is_some_and(|record| record.stars_at_post >= threshold),
+        None => is_key_posted(store, key, repo)
+            .await
+            .context("While checking repo posted")?,
+    })
+}
+
+/// Posts the repo to every platform it is not posted to yet, and returns the platforms when all of
+/// them are done. Each platform is marked separately, so that a failed platform is retried in the
+/// next loop. When reposting, only platforms posted with at least `repost_threshold` stars count as
+/// posted.
+async fn post_repo(
+    config: &Config,
+    store: &mut dyn Store,
+    prefix: &str,
+    repo: &Repo,
+    repost_threshold: Option<usize>,
+    summary: &mut LoopSummary,
+) -> Result<Option<Vec<String>>> {
+    let mut posts: Vec<(&str, String, LocalBoxFuture<Result<()>>)> = Vec::new();
+    let hashtags = &config.hashtags;
+    let format = &config.format;
+    let dry_run = config.dry_run;
+    // Platforms left to later loops without posting
+    let mut deferred = Vec::new();
+
+    if let Some(config) = config
+        .twitter
+        .as_ref()
+        .filter(|config| !config.denylist.contains(repo))
+    {
+        posts.push((
+            "twitter",
+            "twitter".to_string(),
+            async move {
+                let content = make_tweet(
+                    repo,
+                    config.template(),
+                    &format!(
+                        "{}{}",
+                        make_post_extras(repo, &config.extras, format),
+                        make_hashtags(config.hashtags.as_ref().unwrap_or(hashtags))
+                    ),
+                    format,
+                );
+                if dry_run {
+                    return log_dry_run("twitter", &content);
+                }
+                tweet(config, content).await.context("While tweeting")
+            }
+            .boxed_local(),
+        ));
+    }
+
+    for config in config
+        .mastodon
+        .iter()
+        .filter(|config| !config.denylist.contains(repo))
+    {
+        let name = mastodon_platform_name(config);
+        let scheduled_at = match &config.schedule_spread {
+            Some(spread) => match next_mastodon_schedule(spread, &name)? {
+                Some(at) => Some(at),
+                None => {
+                    deferred.push(name);
+                    continue;
+                }
+            },
+            None => None,
+        };
+        let threads = &summary.mastodon_threads;
+        posts.push((
+            "mastodon",
+            name.clone(),
+            async move {
+                let content = make_toot(
+                    repo,
+                    config.template(),
+                    &format!(
+                        "{}{}",
+                        make_post_extras(repo, &config.extras, format),
+                        make_hashtags(config.hashtags.as_ref().unwrap_or(hashtags))
+                    ),
+                    config.content_warning.as_deref(),
+                    format,
+                );
+                if dry_run {
+                    return log_dry_run(config.instance_url.as_str(), &content);
+                }
+                let in_reply_to_id = if config.thread {
+                    mastodon_thread_parent(&HTTP_CLIENT, config, threads, &name).await?
+                } else {
+                    None
+                };
+                let id = toot(
+                    &HTTP_CLIENT,
+                    config,
+                    repo,
+                    &content,
+                    in_reply_to_id.as_deref(),
+                    scheduled_at,
+                )
+                .await
+                .with_context(|| format!("While tooting to {}", config.instance_url))?;
+                if let Some(at) = scheduled_at {
+                    record_mastodon_schedule(&name, at);
+                }
+                if config.thread {
+                    threads.borrow_mut().insert(name, id);
+                }
+                Ok(())
+            }
+            .boxed_local(),
+        ));
+    }
+
+    if let Some(config) = config
+        .bluesky
+        .as_ref()
+        .filter(|config| !config.denylist.contains(repo))
+    {
+        posts.push((
+            "bluesky",
+            "bluesky".to_string(),
+            async move {
+                let hashtags = format!(
+                    "{}{}",
+                    make_post_extras(repo, &config.extras, format),
+                    make_hashtags(config.hashtags.as_ref().unwrap_or(hashtags))
+                );
+                if dry_run {
+                    // Skip fetching the OG image as well
+                    let template = config.template();
+                    return log_dry_run(
+                        "bluesky",
+                        &make_bluesky_post(repo, template, &hashtags, format),
+                    );
+                }
+                post_bluesky(config, repo, &hashtags, format)
+                    .await
+                    .context("While posting to Bluesky")
+            }
+            .boxed_local(),
+        ));
+    }
+
+    if let Some(config) = config
+        .discord
+        .as_ref()
+        .filter(|config| !config.denylist.contains(repo))
+    {
+        posts.push((
+            "discord",
+            "discord".to_string(),
+            async move {
+                if dry_run {
+                    return log_dry_run(
+                        "discord",
+                        &serde_json::to_string(&make_discord_body(repo, format))?,
+                    );
+                }
+                post_discord(&HTTP_CLIENT, config, repo, format)
+                    .await
+                    .context("While posting to Discord")
+            }
+            .boxed_local(),
+        ));
+    }
+
+    if let Some(config) = config
+        .discord_bot
+        .as_ref()
+        .filter(|config| !config.denylist.contains(repo))
+    {
+        posts.push((
+            "discord_bot",
+            "discord_bot".to_string(),
+            async move {
+                if dry_run {
+                    return log_dry_run(
+                        "discord_bot",
+                        &serde_json::to_string(&make_discord_body(repo, format))?,
+                    );
+                }
+                post_discord_bot(&HTTP_CLIENT, config, repo, format)
+                    .await
+                    .context("While posting to Discord as a bot")
+            }
+            .boxed_local(),
+        ));
+    }
+
+    if let Some(config) = config
+        .telegram
+        .as_ref()
+        .filter(|config| !config.denylist.contains(repo))
+    {
+        posts.push((
+            "telegram",
+            "telegram".to_string(),
+            async move {
+                let content = make_telegram_message(repo, format);
+                if dry_run {
+                    return log_dry_run("telegram", &content);
+                }
+                post_telegram(&HTTP_CLIENT, TELEGRAM_API_URL, config, &content)
+                    .await
+                    .context("While posting to Telegram")
+            }
+            .boxed_local(),
+        ));
+    }
+
+    if let Some(config) = config
+        .webhook
+        .as_ref()
+        .filter(|config| !config.denylist.contains(repo))
+    {
+        posts.push((
+            "webhook",
+            "webhook".to_string(),
+            async move {
+                let mut content = make_webhook_text(repo, config.template(), format);
+                content.push_str(&make_post_extras(repo, &config.extras, format));
+                if dry_run {
+                    return log_dry_run("webhook", &content);
+                }
+                post_webhook(&HTTP_CLIENT, config, repo, &content)
+                    .await
+                    .context("While posting to webhook")
+            }
+            .boxed_local(),
+        ));
+    }
+
+    if let Some(config) = config
+        .slack
+        .as_ref()
+        .filter(|config| !config.denylist.contains(repo))
+    {
+        posts.push((
+            "slack",
+            "slack".to_string(),
+            async move {
+                if dry_run {
+                    return log_dry_run("slack", &make_slack_body(repo, format).to_string());
+                }
+                post_slack(&HTTP_CLIENT, config, repo, format)
+                    .await
+                    .context("While posting to Slack")
+            }
+            .boxed_local(),
+        ));
+    }
+
+    if let Some(config) = config
+        .matrix
+        .as_ref()
+        .filter(|config| !config.denylist.contains(repo))
+    {
+        posts.push((
+            "matrix",
+            "matrix".to_string(),
+            async move {
+                if dry_run {
+                    return log_dry_run(
+                        "matrix",
+                        &make_matrix_message(repo, format).formatted_body,
+                    );
+                }
+                post_matrix(&HTTP_CLIENT, config, repo, format)
+                    .await
+                    .context("While posting to Matrix")
+            }
+            .boxed_local(),
+        ));
+    }
+
+    if let Some(config) = config
+        .nostr
+        .as_ref()
+        .filter(|config| !config.denylist.contains(repo))
+    {
+        posts.push((
+            "nostr",
+            "nostr".to_string(),
+            async move {
+                let content = make_nostr_post(
+                    repo,
+                    config.template(),
+                    &format!(
+                        "{}{}",
+                        make_post_extras(repo, &config.extras, format),
+                        make_hashtags(config.hashtags.as_ref().unwrap_or(hashtags))
+                    ),
+                    format,
+                );
+                if dry_run {
+                    return log_dry_run("nostr", &content);
+                }
+                post_nostr(config, &content)
+                    .await
+                    .context("While posting to Nostr")
+            }
+            .boxed_local(),
+        ));
+    }
+
+    if let Some(config) = config
+        .lemmy
+        .as_ref()
+        .filter(|config| !config.denylist.contains(repo))
+    {
+        posts.push((
+            "lemmy",
+            "lemmy".to_string(),
+            async move {
+                if dry_run {
+                    return log_dry_run(
+                        "lemmy",
+                        &serde_json::to_string(&make_lemmy_post(repo, 0))?,
+                    );
+                }
+                post_lemmy(&HTTP_CLIENT, config, repo)
+                    .await
+                    .context("While posting to Lemmy")
+            }
+            .boxed_local(),
+        ));
+    }
+
+    if let Some(config) = config
+        .reddit
+        .as_ref()
+        .filter(|config| !config.denylist.contains(repo))
+    {
+        posts.push((
+            "reddit",
+            "reddit".to_string(),
+            async move {
+                if dry_run {
+                    return log_dry_run(
+                        &format!("r/{}", config.subreddit),
+                        &serde_json::to_string(&make_reddit_submission(config, repo))?,
+                    );
+                }
+                post_reddit(&HTTP_CLIENT, config, repo)
+                    .await
+                    .with_context(|| format!("While posting to r/{}", config.subreddit))
+            }
+            .boxed_local(),
+        ));
+    }
+
+    let mut done = Vec::new();
+    let mut pending = Vec::new();
+    for (platform, name, post) in posts {
+        let key = platform_key(&name, prefix, repo);
+        if is_platform_posted(store, &key, repo, repost_threshold).await? {
+            done.push(name);
+        } else {
+            pending.push((platform, name, key, post));
+        }
+    }
+    let mut all_done = true;
+    for name in deferred {
+        let key = platform_key(&name, prefix, repo);
+        if is_platform_posted(store, &key, repo, repost_threshold).await? {
+            done.push(name);
+        } else {
+            debug!(
+                platform = %name,
+                repo = %repo_slug(repo),
+                "deferred: schedule_spread is full until its next window"
+            );
+            all_done = false;
+        }
+    }
+
+    let results = join_all(
+        pending
+            .into_iter()
+            .map(|(platform, name, key, post)| async move {
+                let timer = POST_DURATION_SECONDS
+                    .with_label_values(&[platform])
+                    .start_timer();
+                // A post may take a few requests, like uploading an image first. This also bounds
+                // the Twitter client, which can't be given a timeout.
+                let result = match tokio::time::timeout(post_timeout(), post).await {
+                    Ok(result) => result.map_err(PostError::from),
+                    Err(_) => Err(PostError::Timeout(platform)),
+                };
+                timer.observe_duration();
+                let failures = ALERTS.lock().unwrap().record_post(&name, result.is_ok());
+                let result = match result {
+                    Ok(()) => {
+                        if !dry_run {
+                            REPOS_POSTED.with_label_values(&[platform]).inc();
+                        }
+                        Ok((name, key))
+                    }
+                    Err(error) => {
+                        POST_ERRORS
+                            .with_label_values(&[platform, error.kind()])
+                            .inc();
+                        error!(
+                            platform,
+                            repo = %repo_slug(repo),
+                            error = %format!("{:#}", error),
+                            "failed to post"
+                        );
+                        if failures >= ALERT_POST_FAILURES {
+                            let message = alert_message(
+                                &format!(
+                                    "Failed to post to {} {} times in a row: {}",
+                                    name,
+                                    failures,
+                                    error.kind()
+                                ),
+                                error.status(),
+                            );
+                            send_alert(config, &format!("post:{}", name), &message).await;
+                        }
+                        Err((name, error))
+                    }
+                };
+                (platform, result)
+            }),
+    )
+    .await;
+    summary.errors += results.iter().filter(|(_, result)| result.is_err()).count();
+
+    // Nothing is marked in dry-run mode, so the same repos are logged again in the next loop
+    if dry_run {
+        return Ok(None);
+    }
+
+    for (platform, result) in results {
+        match result {
+            Ok((name, key)) => {
+                *summary.posted.entry(platform).or_default() += 1;
+                mark_posted_key(
+                    store,
+                    &key,
+                    repo,
+                    vec![name.clone()],
+                    config.post_ttl(&name),
+                )
+                .await
+                .context("While marking repo posted")?;
+                done.push(name);
+            }
+            Err((name, error)) => {
+                // Not deadlettered when it would only be rejected again
+                if error.is_retryable() {
+                    summary.failures.push((name, format!("{:#}", error)));
+                }
+                all_done = false;
+            }
+        }
+    }
+    Ok(if all_done { Some(done) } else { None })
+}
+
+/// Posts the repo and returns whether every platform is done.
+/// The repo key is only marked once every platform is done, to skip it in later loops.
+async fn post_and_mark_repo(
+    config: &Config,
+    store: &mut dyn Store,
+    prefix: &str,
+    repo: &Repo,
+    repost_threshold: Option<usize>,
+    summary: &mut LoopSummary,
+) -> Result<bool> {
+    let platforms = match post_repo(config, store, prefix, repo, repost_threshold, summary).await? {
+        Some(platforms) => platforms,
+        None => return Ok(false),
+    };
+    mark_posted_repo(store, prefix, repo, platforms, config.min_post_ttl())
+        .await
+        .context("While marking repo posted")?;
+
+    if let Some(config) = &config.feed {
+        if let Err(error) = append_feed(config, repo).context("While writing Atom feed") {
+            error!("{:#?}", error);
+        }
+    }
+
+    info!(
+        repo = %repo_slug(repo),
+        stars = repo.stars,
+        stars_period = repo.stars_period,
+        forks = repo.forks,
+        "posted"
+    );
+    Ok(true)
+}
+
+/// Deadletters the failed posts of the repo under the key of each platform. A post failed again
+/// keeps the attempts stored, which only count failed retries, and is given up after
+/// `DEADLETTER_MAX_ATTEMPTS` of them. Failures are only logged, as the repo is still posted again
+/// in the next loops while it is trending.
+async fn deadletter_failures(
+    store: &mut dyn Store,
+    prefix: &str,
+    repo: &Repo,
+    retried: bool,
+    summary: &mut LoopSummary,
+) {
+    for (platform, error) in std::mem::take(&mut summary.failures) {
+        let key = platform_key(&platform, prefix, repo);
+        let result = async {
+            let attempts = store
+                .deadletter(&key)
+                .await?
+                .and_then(|value| serde_json::from_str::<DeadLetter>(&value).ok())
+                .map(|deadletter| deadletter.attempts)
+                .unwrap_or_default()
+                + retried as u32;
+            if attempts >= DEADLETTER_MAX_ATTEMPTS {
+                warn!(
+                    platform = %platform,
+                    repo = %repo_slug(repo),
+                    "giving up posting after {} retries",
+                    attempts
+                );
+                return store.remove_deadletter(&key).await;
+            }
+            let deadletter = DeadLetter {
+                prefix: prefix.to_string(),
+                repo: repo.clone(),
+                period: repo.period,
+                created_at: repo.created_at.map(OffsetDateTime::unix_timestamp),
+                platform: platform.clone(),
+                error,
+                attempts,
+                failed_at: now_ts(),
+            };
+            let value = serde_json::to_string(&deadletter)?;
+            if !store
+                .set_deadletter(&key, &value, DEADLETTER_MAX_LEN)
+                .await?
+            {
+                warn!(
+                    platform = %platform,
+                    repo = %repo_slug(repo),
+                    "dropping failed post, as {} posts are deadlettered already",
+                    DEADLETTER_MAX_LEN
+                );
+            }
+            Ok(())
+        }
+        .await;
+        if let Err(error) = result {
+            error!(
+                platform = %platform,
+                repo = %repo_slug(repo),
+                error = %format!("{:#}", error),
+                "failed to deadletter post"
+            );
+        }
+    }
+}
+
+/// Retries the deadlettered posts which are due, oldest first and a few in each loop. Each post
+/// is removed only once it is done or given up, so that the rest are kept when a retry fails.
+async fn retry_deadletters(config: &Config, store: &mut dyn Store) -> Result<()> {
+    if config.dry_run {
+        return Ok(());
+    }
+    let now = now_ts();
+    let mut deadletters = Vec::new();
+    for (key, value) in store
+        .deadletters()
+        .await
+        .context("While reading deadletters")?
+    {
+        match serde_json::from_str::<DeadLetter>(&value) {
+            Ok(deadletter) => deadletters.push((key, deadletter)),
+            Err(error) => {
+                warn!(key = %key, error = %error, "dropping invalid deadletter");
+                store.remove_deadletter(&key).await?;
+            }
+        }
+    }
+    deadletters.retain(|(_, deadletter)| deadletter.retry_at() <= now);
+    deadletters.sort_by_key(|(_, deadletter)| deadletter.failed_at);
+
+    let mut retried = 0;
+    for (key, deadletter) in deadletters {
+        if retried >= DEADLETTER_RETRIES_PER_LOOP || is_paused(config, store).await? {
+            break;
+        }
+        match retry_deadletter(config, store, &key, deadletter).await {
+            Ok(false) => continue,
+            Ok(true) => {}
+            // Kept to be retried in a later loop
+            Err(error) => error!(
+                key = %key,
+                error = %format!("{:#}", error),
+                "failed to retry deadlettered post"
+            ),
+        }
+        retried += 1;
+        tokio::time::sleep(jittered(
+            config.post_interval(),
+            config.interval.post_jitter_secs,
+        ))
+        .await;
+    }
+    Ok(())
+}
+
+/// Retries a deadlettered post, and returns whether it was posted again rather than found done.
+async fn retry_deadletter(
+    config: &Config,
+    store: &mut dyn Store,
+    key: &str,
+    deadletter: DeadLetter,
+) -> Result<bool> {
+    let prefix = deadletter.prefix.clone();
+    let platform = deadletter.platform.clone();
+    let attempts = deadletter.attempts;
+    let error = deadletter.error.clone();
+    let repo = deadletter.into_repo();
+
+    // Posted in a later loop after all, or denylisted since
+    if is_key_posted(store, key, &repo).await?
+        || is_repo_posted(store, &prefix, &repo).await?
+        || config.denylist.contains(&repo)
+    {
+        store.remove_deadletter(key).await?;
+        return Ok(false);
+    }
+
+    info!(
+        platform = %platform,
+        repo = %repo_slug(&repo),
+        error = %error,
+        attempts,
+        "retrying failed post"
+    );
+    let mut summary = LoopSummary::default();
+    post_and_mark_repo(config, store, &prefix, &repo, None, &mut summary).await?;
+    let failed = summary.failures.iter().any(|(name, _)| *name == platform);
+    deadletter_failures(store, &prefix, &repo, true, &mut summary).await;
+    // Posted, rejected for good, or the platform is no longer configured
+    if !failed {
+        store.remove_deadletter(key).await?;
+    }
+    Ok(true)
+}
+
+/// Posts a repo given on the command line, regardless of whether it is trending.
+/// The global denylist is not applied, and posted repos are posted again only when forced.
+async fn post_single_repo(
+    config: &Config,
+    store: &mut dyn Store,
+    repo: &str,
+    force: bool,
+) -> Result<()> {
+    let (author, name) = repo
+        .split_once('/')
+        .filter(|(author, name)| !author.is_empty() && !name.is_empty())
+        .context("Repo must be given as author/name")?;
+    let repo = retry_fetch(|| get_repo(&config.github, author, name))
+        .await
+        .context("While fetching repo")?;
+
+    // Marked with the keys of Rust and daily range, so that it is not posted again when trending
+    let prefix = "";
+    let mut threshold = None;
+    if is_repo_posted(store, prefix, &repo)
+        .await
+        .context("While checking repo posted")?
+    {
+        if !force {
+            bail!(
+                "{}/{} is already posted, use --force to post it again",
+                repo.author,
+                repo.name
+            );
+        }
+        // No platform is posted with this many stars, so that every platform posts it again
+        threshold = Some(usize::MAX);
+    }
+
+    let mut summary = LoopSummary::default();
+    if !post_and_mark_repo(config, store, prefix, &repo, threshold, &mut summary).await? {
+        bail!(
+            "Failed to post {}/{} to some platforms",
+            repo.author,
+            repo.name
+        );
+    }
+    Ok(())
+}
+
+async fn post_trending(
+    config: &Config,
+    store: &mut dyn Store,
+    language: &str,
+    since: Since,
+    summary: &mut LoopSummary,
+) -> Result<()> {
+    let prefix = key_prefix(language, since);
+    let mut repos = fetch_repos(&config.github, language, since)
+        .await
+        .context("While fetching repo")?;
+    REPOS_FETCHED.inc_by(repos.len() as u64);
+    summary.fetched += repos.len();
+    LAST_SUCCESSFUL_FETCH_TIMESTAMP.set(now_ts() as i64);
+    record_progress();
+    record_heartbeat(config, store, HEARTBEAT_LAST_FETCH).await;
+
+    sort_repos(&mut repos, config.trending.order);
+    for repo in repos {
+        if let Some(min_stars) = config.trending.min_stars {
+            if repo.stars < min_stars {
+                debug!(
+                    repo = %repo_slug(&repo),
+                    stars = repo.stars,
+                    "skipped: less than {} stars",
+                    min_stars
+                );
+                summary.filtered += 1;
+                continue;
+            }
+        }
+        if let Some(min_stars_period) = config.trending.min_stars_period {
+            if repo.stars_period < min_stars_period {
+                debug!(
+                    repo = %repo_slug(&repo),
+                    stars_period = repo.stars_period,
+                    "skipped: less than {} stars during {} range",
+                    min_stars_period,
+                    since.as_str()
+                );
+                summary.filtered += 1;
+                continue;
+            }
+        }
+        if let Some(langs) = &config.trending.allowed_description_langs {
+            if let Some(lang) = detect_description_lang(&repo.description).filter(|lang| {
+                !langs
+                    .iter()
+                    .any(|code| code.eq_ignore_ascii_case(lang.code()))
+            }) {
+                debug!(
+                    repo = %repo_slug(&repo),
+                    "skipped: description detected as {}",
+                    lang.code()
+                );
+                summary.filtered += 1;
+                continue;
+            }
+        }
+        if config.trending.skip_empty_description && repo.description.trim().is_empty() {
+            debug!(repo = %repo_slug(&repo), "skipped: no description");
+            summary.filtered += 1;
+            continue;
+        }
+
+        if config.denylist.contains(&repo) {
+            summary.denylisted += 1;
+            continue;
+        }
+        let mut threshold = None;
+        if is_repo_posted(store, &prefix, &repo)
+            .await
+            .context("While checking repo posted")?
+        {
+            let record = get_posted_record(store, &prefix, &repo)
+                .await
+                .context("While reading posted repo")?;
+            threshold = record.as_ref().and_then(|record| {
+                let star_delta = config.trending.repost_on_star_delta?;
+                repost_threshold(record, &repo, star_delta)
+            });
+            if threshold.is_none() {
+                if let Some(record) = record {
+                    debug!(
+                        repo = %repo_slug(&repo),
+                        stars = repo.stars,
+                        "skipped: posted at {} with {} stars",
+                        record.posted_at,
+                        record.stars_at_post
+                    );
+                }
+                summary.already_posted += 1;
+                continue;
+            }
+        }
+
+        if summary.reached_max_posts(&config.trending) {
+            break;
+        }
+        // Not marked as posted, so that it can be posted in the next loops
+        if summary.reached_max_per_author(&config.trending, &repo.author) {
+            debug!(
+                repo = %repo_slug(&repo),
+                "skipped: posted {} repos of the author in this loop",
+                config.trending.max_per_author_per_run.unwrap_or_default()
+            );
+            summary.filtered += 1;
+            continue;
+        }
+        // Checked before each post, as a loop can take long
+        if is_paused(config, store).await? {
+            summary.paused = true;
+            break;
+        }
+        let posted = post_and_mark_repo(config, store, &prefix, &repo, threshold, summary).await?;
+        if posted {
+            record_progress();
+            record_heartbeat(config, store, HEARTBEAT_LAST_POST).await;
+        }
+        // Nothing is marked in dry-run mode, but the logged posts still count toward the limits
+        if posted || config.dry_run {
+            summary.repos_posted += 1;
+            *summary
+                .authors_posted
+                .entry(repo.author.to_lowercase())
+                .or_default() += 1;
+        }
+        deadletter_failures(store, &prefix, &repo, false, summary).await;
+
+        // Without waiting after the last post of the loop
+        if summary.reached_max_posts(&config.trending) {
+            break;
+        }
+        if !config.dry_run {
+            tokio::time::sleep(jittered(
+                config.post_interval(),
+                config.interval.post_jitter_secs,
+            ))
+            .await;
+        }
+    }
+
+    Ok(())
+}
+
+static REPOS_FETCHED: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!("repos_fetched_total", "Number of fetched trending repos").unwrap()
+});
+static REPOS_POSTED: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "repos_posted_total",
+        "Number of repos posted to each platform",
+        &["platform"]
+    )
+    .unwrap()
+});
+static POST_ERRORS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "post_errors_total",
+        "Number of failed posts to each platform, by the kind of error",
+        &["platform", "kind"]
+    )
+    .unwrap()
+});
+static POST_DURATION_SECONDS: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        "post_duration_seconds",
+        "Time taken to post to each platform",
+        &["platform"]
+    )
+    .unwrap()
+});
+static LAST_LOOP_REPOS: Lazy<IntGaugeVec> = Lazy::new(|| {
+    register_int_gauge_vec!(
+        "last_loop_repos",
+        "Number of repos of each outcome in the last loop",
+        &["outcome"]
+    )
+    .unwrap()
+});
+static LAST_SUCCESSFUL_FETCH_TIMESTAMP: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!(
+        "last_successful_fetch_timestamp",
+        "Unix timestamp of the last successful fetch of trending repos"
+    )
+    .unwrap()
+});
+
+async fn serve_metrics(_req: hyper::Request<hyper::Body>) -> Result<hyper::Response<hyper::Body>> {
+    let mut buffer = Vec::new();
+    TextEncoder::new().encode(&prometheus::gather(), &mut buffer)?;
+    Ok(hyper::Response::builder()
+        .header(hyper::header::CONTENT_TYPE, prometheus::TEXT_FORMAT)
+        .body(buffer.into())?)
+}
+
+async fn run_metrics_server(addr: SocketAddr) -> Result<()> {
+    let server =
+        hyper::Server::try_bind(&addr)?.serve(hyper::service::make_service_fn(|_| async {
+            Ok::<_, Infallible>(hyper::service::service_fn(serve_metrics))
+        }));
+    info!("serving metrics on {}", addr);
+    Ok(server.await?)
+}
+
+/// UNIX timestamp of the last progress of the loops, like a fetch, a post or a finished loop.
+/// Updated while a loop is still running, so that a long loop posting many repos is not taken for
+/// a stuck one.
+static LAST_PROGRESS: AtomicU64 = AtomicU64::new(0);
+
+fn record_progress() {
+    LAST_PROGRESS.store(now_ts(), Ordering::Relaxed);
+}
+
+/// Whether the last progress is recent enough for the bot to be considered healthy.
+fn is_healthy(last_progress: u64, now: u64, fetch_interval: u64) -> bool {
+    now.saturating_sub(last_progress) <= 2 * fetch_interval
+}
+
+async fn run_health_server(addr: SocketAddr, fetch_interval: u64) -> Result<()> {
+    let server = hyper::Server::try_bind(&addr)?.serve(hyper::service::make_service_fn(
+        move |_| async move {
+            Ok::<_, Infallible>(hyper::service::service_fn(
+                move |req: hyper::Request<hyper::Body>| {
+                    let last_progress = LAST_PROGRESS.load(Ordering::Relaxed);
+                    async move {
+                        let status = if req.uri().path() != "/healthz" {
+                            hyper::StatusCode::NOT_FOUND
+                        } else if is_healthy(last_progress, now_ts(), fetch_interval) {
+                            hyper::StatusCode::OK
+                        } else {
+                            hyper::StatusCode::SERVICE_UNAVAILABLE
+                        };
+                        hyper::Response::builder()
+                            .status(status)
+                            .body(hyper::Body::empty())
+                    }
+                },
+            ))
+        },
+    ));
+    info!("serving healthcheck on {}", addr);
+    Ok(server.await?)
+}
+
+/// Posts trending repos on GitHub to social media.
+#[derive(Parser)]
+#[command(version)]
+pub struct Args {
+    /// Path of the config file
+    #[arg(short, long, default_value = "./config.toml")]
+    config: PathBuf,
+    /// Path of the config file, kept for compatibility with older versions
+    #[arg(hide = true, conflicts_with = "config")]
+    config_path: Option<PathBuf>,
+    /// Post a single pass and exit
+    #[arg(long)]
+    once: bool,
+    /// Log posts instead of sending them
+    #[arg(long)]
+    dry_run: bool,
+    /// Post the given repo and exit
+    #[arg(long, value_name = "AUTHOR/NAME")]
+    post: Option<String>,
+    /// Post the repo given with --post even if it is already posted
+    #[arg(long, requires = "post")]
+    force: bool,
+    /// Copy redis keys of posted repos written by older versions to the key of each platform,
+    /// and exit
+    #[arg(long, conflicts_with_all = ["once", "post"])]
+    migrate: bool,
+    /// Print the effective config after environment overrides without secrets, and exit with an
+    /// error if it's invalid or has unknown keys
+    #[arg(long, conflicts_with_all = ["once", "post", "migrate"])]
+    check_config: bool,
+}
+
+fn check_config(config: &Config, unknown_keys: &[String]) -> Result<()> {
+    println!("{}", config.describe());
+    for key in unknown_keys {
+        println!("unknown key: {}", key);
+    }
+    config.validate()?;
+    if !unknown_keys.is_empty() {
+        bail!("Unknown keys in the config file, which may be misspelled");
+    }
+    println!("Config is valid");
+    Ok(())
+}
+
+/// Runs the bot as the binary does with the arguments.
+pub async fn run(args: Args) -> Result<()> {
+    let config_file_path = args.config_path.as_ref().unwrap_or(&args.config);
+    let (mut config, unknown_keys) =
+        read_config(config_file_path).context("While reading config file")?;
+    init_logging(config.log_format)?;
+    if args.check_config {
+        return check_config(&config, &unknown_keys);
+    }
+    // Not an error, so that configs written for newer versions still load
+    for key in &unknown_keys {
+        warn!(key = %key, "ignoring unknown config key, which may be misspelled");
+    }
+    config.validate()?;
+    config.dry_run |= args.dry_run;
+    // Kept from the first call when `run` is called again in the same process
+    let _ = HTTP_CONFIG.set(config.http_config()?);
+    let _ = GITHUB_HOST.set(config.github.host());
+
+    if args.migrate {
+        let redis = config
+            .redis
+            .as_ref()
+            .context("Only redis has keys of older versions to migrate")?;
+        let migrated = RedisStore::connect(redis)
+            .await?
+            .migrate_legacy_keys(&config.platform_names())
+            .await
+            .context("While migrating keys")?;
+        println!("Migrated {} posted repos", migrated);
+        return Ok(());
+    }
+
+    let mut store = open_store(&config).await?;
+
+    if let Some(repo) = &args.post {
+        return post_single_repo(&config, store.as_mut(), repo, args.force).await;
+    }
+
+    // A single pass for cron jobs and timers, which don't need the metrics and healthcheck
+    if args.once {
+        if let Err(error) = main_loop(&config, store.as_mut()).await {
+            let message = alert_message("Failed to post trending repos", error_status(&error));
+            send_alert(&config, "loop", &message).await;
+            return Err(error);
+        }
+        return Ok(());
+    }
+
+    if let Some(addr) = config.metrics_bind {
+        tokio::spawn(async move {
+            if let Err(error) = run_metrics_server(addr)
+                .await
+                .context("While serving metrics")
+            {
+                error!("{:#?}", error);
+            }
+        });
+    }
+
+    // Count the startup as progress, so that the bot is healthy until the first fetch
+    record_progress();
+    if let Some(addr) = config.health_bind {
+        let fetch_interval = config.interval.fetch_interval + config.interval.fetch_jitter_secs;
+        tokio::spawn(async move {
+            if let Err(error) = run_health_server(addr, fetch_interval)
+                .await
+                .context("While serving healthcheck")
+            {
+                error!("{:#?}", error);
+            }
+        });
+    }
+
+    loop {
+        let res = main_loop(&config, store.as_mut()).await;
+        match res {
+            Ok(summary) => {
+                summary.observe();
+                record_progress();
+            }
+            Err(e) => {
+                error!(error = %format!("{:#}", e), "failed to post trending repos");
+                let message = alert_message("Failed to post trending repos", error_status(&e));
+                send_alert(&config, "loop", &message).await;
+            }
+        }
+
+        tokio::time::sleep(jittered(
+            config.interval.fetch_interval,
+            config.interval.fetch_jitter_secs,
+        ))
+        .await;
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::needless_update)]
+mod tests {
+    use serde::Deserialize;
+    use unicode_segmentation::UnicodeSegmentation;
+
+    use super::{
+        alert_message, append_feed, backfill_descriptions, bluesky_login_hint, cased_key,
+        deadletter_failures, dedup_repos, default_template, deserialize_one_or_many,
+        detect_description_lang, escape_mentions, format_stars, get_posted_key_record,
+        get_posted_record, is_bluesky_identifier, is_healthy, is_key_posted, is_language_tag,
+        is_legacy_posted_value, is_truthy, jittered, key_prefix, legacy_repo_key,
+        make_bluesky_link_facet, make_bluesky_post, make_bluesky_post_input, make_bluesky_record,
+        make_bluesky_threadgate_input, make_digest, make_hashtags, make_idempotency_key,
+        make_lemmy_post, make_matrix_message, make_nostr_event, make_post_extras,
+        make_reddit_submission, make_slack_body, make_telegram_message, make_toot, make_tweet,
+        mark_posted_key, mark_posted_repo, matrix_send_url, next_scheduled_at, parse_config,
+        parse_count, parse_retry_after, parse_trending, platform_key, post_discord, post_slack,
+        post_telegram, post_trending, render_post, repo_key, repo_uri, repost_threshold,
+        search_query, send_with_rate_limit, sign_nostr_id, sort_repos, toot, trending_url,
+        weighted_shuffle, Alerts, Args, BlueskyConfig, Config, DeadLetter, DenylistConfig,
+        DiscordConfig, Duration, FeedConfig, FetchError, FormatConfig, GithubConfig, HashMap,
+        LengthUnit, LogFormat, LoopSummary, MastodonConfig, MatrixConfig, PostError, PostExtras,
+        PostOrder, PostedRecord, RedditConfig, RedditSubmitResponse, Repo,
+        SearchRepositoriesOutput, SeedableRng, Since, SlackConfig, SqliteStore, StarFormat, StdRng,
+        Store, TelegramConfig, TrendingConfig, Url, ALERT_THROTTLE, BLUESKY_POST_LENGTH,
+        DEADLETTER_MAX_ATTEMPTS, DEFAULT_TEMPLATE, MASTODON_FIXED_URL_LENGTH, TOOT_LENGTH,
+        TWEET_LENGTH,
+    };
+
+    const TEST_HTML: &str = include_str!("../testdata/test.html");
+
+    macro_rules! repo {
+        ( $author:expr, $name:expr, $description:expr, $stars:expr $(, $field:ident: $value:expr )* ) => {
+            Repo {
+                author: $author.to_string(),
+                name: $name.to_string(),
+                description: $description.to_string(),
+                stars: $stars,
+                $( $field: $value, )*
+                ..Default::default()
+            }
+        };
+    }
+
+    #[test]
+    fn test_denylistconfig_contains() {
+        assert!(!DenylistConfig {
+            authors: vec![],
+            names: vec![],
+            descriptions: vec![],
+            ..Default::default()
+        }
+        .contains(&repo!("foo", "bar", "somelongdescription", 0)));
+        assert!(DenylistConfig {
+            authors: vec!["foo".to_string()],
+            names: vec![],
+            descriptions: vec![],
+            ..Default::default()
+        }
+        .contains(&repo!("foo", "bar", "somelongdescription", 0)));
+        assert!(!DenylistConfig {
+            authors: vec!["bar".to_string()],
+            names: vec![],
+            descriptions: vec![],
+            ..Default::default()
+        }
+        .contains(&repo!("foo", "bar", "somelongdescription", 0)));
+        assert!(DenylistConfig {
+            authors: vec![],
+            names: vec!["bar".to_string()],
+            descriptions: vec![],
+            ..Default::default()
+        }
+        .contains(&repo!("foo", "bar", "somelongdescription", 0)));
+        assert!(!DenylistConfig {
+            authors: vec![],
+            names: vec!["foo".to_string()],
+            descriptions: vec![],
+            ..Default::default()
+        }
+        .contains(&repo!("foo", "bar", "somelongdescription", 0)));
+        assert!(DenylistConfig {
+            authors: vec![],
+            names: vec![],
+            descriptions: vec!["long".to_string()],
+            ..Default::default()
+        }
+        .contains(&repo!("foo", "bar", "somelongdescription", 0)));
+        assert!(!DenylistConfig {
+            authors: vec![],
+            names: vec![],
+            descriptions: vec!["foo".to_string()],
+            ..Default::default()
+        }
+        .contains(&repo!("foo", "bar", "somelongdescription", 0)));
+        assert!(DenylistConfig {
+            authors: vec![],
+            names: vec![],
+            descriptions: vec!["Long".to_string()],
+            ..Default::default()
+        }
+        .contains(&repo!("foo", "bar", "someloNgdescription", 0)));
+
+        let denylist: DenylistConfig = toml::from_str(
+            r#"
+            name_patterns = ["^awesome-"]
+            author_patterns = ["(?i)-bot$"]
+            description_patterns = ["(?i)\\bairdrop\\b"]
+            "#,
+        )
+        .unwrap();
+        assert!(denylist.contains(&repo!("foo", "awesome-rust", "", 0)));
+        assert!(!denylist.contains(&repo!("foo", "not-awesome-rust", "", 0)));
+        assert!(denylist.contains(&repo!("foo-Bot", "bar", "", 0)));
+        assert!(denylist.contains(&repo!("foo", "bar", "Free Airdrop now", 0)));
+        assert!(!denylist.contains(&repo!("foo", "bar", "airdropped", 0)));
+
+        assert!(toml::from_str::<DenylistConfig>(r#"name_patterns = ["("]"#).is_err());
+    }
+
+    #[test]
+    fn test_denylistconfig_age_and_topics() {
+        let now = time::macros::datetime!(2023-07-10 00:00 UTC);
+        let denylist = DenylistConfig {
+            min_age_days: Some(7),
+            max_age_days: Some(365),
+            denied_topics: vec!["Crypto".to_string()],
+            ..Default::default()
+        };
+        let created = |created_at| repo!("foo", "bar", "", 0, created_at: Some(created_at));
+        assert!(denylist.contains_at(&created(time::macros::datetime!(2023-07-05 00:00 UTC)), now));
+        assert!(!denylist.contains_at(&created(time::macros::datetime!(2023-06-01 00:00 UTC)), now));
+        assert!(denylist.contains_at(&created(time::macros::datetime!(2020-01-01 00:00 UTC)), now));
+        assert!(!denylist.contains_at(&repo!("foo", "bar", "", 0), now));
+        assert!(denylist.contains_at(
+            &repo!("foo", "bar", "", 0, topics: vec!["crypto".to_string()]),
+            now
+        ));
+        assert!(!denylist.contains_at(
+            &repo!("foo", "bar", "", 0, topics: vec!["rust".to_string()]),
+            now
+        ));
+    }
+
+    #[test]
+    fn test_denylistconfig_default() {
+        let denylist: DenylistConfig = toml::from_str(r#"authors = ["foo"]"#).unwrap();
+        assert!(denylist.contains(&repo!("foo", "bar", "", 0)));
+        assert!(!denylist.contains(&repo!("bar", "foo", "", 0)));
+        assert!(!DenylistConfig::default().contains(&repo!("foo", "bar", "", 0)));
+    }
+
+    #[test]
+    fn test_mastodon_one_or_many() {
+        #[derive(Deserialize)]
+        struct Config {
+            #[serde(default, deserialize_with = "deserialize_one_or_many")]
+            mastodon: Vec<MastodonConfig>,
+        }
+
+        let config: Config = toml::from_str(
+            r#"
+            [mastodon]
+            instance_url = "https://mastodon.social"
+            access_token = "foo"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.mastodon.len(), 1);
+
+        let config: Config = toml::from_str(
+            r#"
+            [[mastodon]]
+            instance_url = "https://mastodon.social"
+            access_token = "foo"
+
+            [[mastodon]]
+            instance_url = "https://botsin.space"
+            access_token = "bar"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.mastodon.len(), 2);
+        assert_eq!(config.mastodon[1].access_token, "bar");
+
+        let config: Config = toml::from_str("").unwrap();
+        assert!(config.mastodon.is_empty());
+    }
+
+    #[test]
+    fn test_parse_trending() {
+        let repos = parse_trending(TEST_HTML.to_string()).unwrap();
+        assert_eq!(
+            repos[..5].to_vec(),
+            vec![
+                repo!(
+                    "servo",
+                    "servo",
+                    "The Servo Browser Engine",
+                    18622,
+                    stars_period: 239,
+                    forks: 2498,
+                    language: Some("Rust".to_string()),
+                    contributors: vec!["Ms2ger".to_string(), "emilio".to_string(), "jdm".to_string(), "SimonSapin".to_string(), "nox".to_string()]
+                ),
+                repo!(
+                    "timberio",
+                    "vector",
+                    "A high-performance, end-to-end observability data platform.",
+                    5672,
+                    stars_period: 7,
+                    forks: 352,
+                    language: Some("Rust".to_string()),
+                    contributors: vec!["binarylogic".to_string(), "lukesteensen".to_string(), "LucioFranco".to_string(), "a-rodin".to_string(), "bruceg".to_string()]
+                ),
+                repo!(
+                    "rust-lang",
+                    "rust",
+                    "Empowering everyone to build reliable and efficient software.",
+                    49626,
+                    stars_period: 30,
+                    forks: 7307,
+                    language: Some("Rust".to_string()),
+                    contributors: vec!["bors".to_string(), "brson".to_string(), "alexcrichton".to_string(), "Centril".to_string(), "nikomatsakis".to_string()]
+                ),
+                repo!(
+                    "wasmerio",
+                    "wasmer",
+                    "🚀 The leading WebAssembly Runtime supporting WASI and Emscripten",
+                    6806,
+                    stars_period: 10,
+                    forks: 271,
+                    language: Some("Rust".to_string()),
+                    contributors: vec!["syrusakbary".to_string(), "Hywan".to_string(), "MarkMcCaskey".to_string(), "nlewycky".to_string(), "losfair".to_string()]
+                ),
+                repo!(
+                    "firecracker-microvm",
+                    "firecracker",
+                    "Secure and fast microVMs for serverless computing.",
+                    13092,
+                    stars_period: 7,
+                    forks: 919,
+                    language: Some("Rust".to_string()),
+                    contributors: vec!["dianpopa".to_string(), "acatangiu".to_string(), "andreeaflorescu".to_string(), "alexandruag".to_string(), "aghecenco".to_string()]
+                ),
+            ]
+        );
+
+        // A row of the weekly range
+        let html = "<div class=\"Box\"><article class=\"Box-row\">
+            <h1 class=\"h3\"><a href=\"/tokio-rs/tokio\">tokio-rs / tokio</a></h1>
+            <p class=\"my-1\">A runtime for writing reliable asynchronous applications with Rust.</p>
+            <div>
+              <span itemprop=\"programmingLanguage\">Rust</span>
+              <a class=\"mr-3\" href=\"/tokio-rs/tokio/stargazers\"><svg aria-label=\"star\"></svg> 21,337</a>
+              <a class=\"mr-3\" href=\"/tokio-rs/tokio/network/members\"><svg aria-label=\"fork\"></svg> 2,013</a>
+              <span class=\"d-inline-block float-sm-right\"><svg></svg> 1,234 stars this week</span>
+            </div>
+        </article></div>";
+        assert_eq!(
+            parse_trending(html.to_string()).unwrap(),
+            vec![repo!(
+                "tokio-rs",
+                "tokio",
+                "A runtime for writing reliable asynchronous applications with Rust.",
+                21337,
+                stars_period: 1234,
+                forks: 2013,
+                language: Some("Rust".to_string())
+            )]
+        );
+    }
+
+    #[test]
+    fn test_format_stars() {
+        assert_eq!(format_stars(49626, StarFormat::Plain), "49626");
+        assert_eq!(format_stars(49626, StarFormat::Grouped), "49,626");
+        assert_eq!(format_stars(1234567, StarFormat::Grouped), "1,234,567");
+        assert_eq!(format_stars(123, StarFormat::Grouped), "123");
+        assert_eq!(format_stars(0, StarFormat::Grouped), "0");
+
+        let format = FormatConfig {
+            star_format: StarFormat::Grouped,
+            ..Default::default()
+        };
+        let repo = repo!("foo", "bar", "a".repeat(300), 49626);
+        let tweet = make_tweet(&repo, DEFAULT_TEMPLATE, "", &format);
+        assert_eq!(tweet.len(), TWEET_LENGTH);
+        assert!(tweet.ends_with(" ★49,626 https://github.com/foo/bar"));
+    }
+
+    #[test]
+    fn test_truncation_suffix() {
+        let repo = repo!("foo", "bar", "a".repeat(400), 42);
+        let default = make_bluesky_post(&repo, DEFAULT_TEMPLATE, "", &FormatConfig::default());
+        assert_eq!(default.graphemes(true).count(), BLUESKY_POST_LENGTH);
+        assert!(default.ends_with("a ... ★42 https://github.com/foo/bar"));
+
+        let format = FormatConfig {
+            truncation_suffix: Some("…".to_string()),
+            ..Default::default()
+        };
+        let post = make_bluesky_post(&repo, DEFAULT_TEMPLATE, "", &format);
+        assert_eq!(post.graphemes(true).count(), BLUESKY_POST_LENGTH);
+        assert!(post.ends_with("a… ★42 https://github.com/foo/bar"));
+        // The single character suffix leaves 3 more characters for the description
+        assert_eq!(post.matches('a').count(), default.matches('a').count() + 3);
+
+        // Twitter counts in bytes, where the ellipsis is 3 bytes
+        let tweet = make_tweet(&repo, DEFAULT_TEMPLATE, "", &format);
+        assert_eq!(tweet.len(), TWEET_LENGTH);
+        assert!(tweet.ends_with("a… ★42 https://github.com/foo/bar"));
+    }
+
+    #[test]
+    fn test_star_symbol() {
+        // A family emoji is a single grapheme of 5 code points and 18 bytes
+        let family = "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}";
+        let format = FormatConfig {
+            star_symbol: Some(family.to_string()),
+            ..Default::default()
+        };
+        let repo = repo!("foo", "bar", "가".repeat(600), 42);
+
+        let tweet = make_tweet(&repo, DEFAULT_TEMPLATE, "", &format);
+        assert!(tweet.len() <= TWEET_LENGTH);
+        assert!(tweet.ends_with(&format!(" {}42 https://github.com/foo/bar", family)));
+
+        let toot = make_toot(&repo, DEFAULT_TEMPLATE, "", None, &format);
+        let url = repo_uri(&repo);
+        let length = toot.chars().count() - url.chars().count() + MASTODON_FIXED_URL_LENGTH;
+        assert!(length <= TOOT_LENGTH);
+        assert!(toot.ends_with(&format!(" {}42 https://github.com/foo/bar", family)));
+
+        let post = make_bluesky_post(&repo, DEFAULT_TEMPLATE, "", &format);
+        assert_eq!(post.graphemes(true).count(), BLUESKY_POST_LENGTH);
+
+        assert_eq!(
+            make_slack_body(&repo, &format)["blocks"][1]["elements"][0]["text"],
+            format!("{}42", family)
+        );
+    }
+
+    #[test]
+    fn test_parse_count() {
+        assert_eq!(parse_count("1,234"), Some(1234));
+        assert_eq!(parse_count("12.3k"), Some(12300));
+        assert_eq!(parse_count("1m"), Some(1_000_000));
+        assert_eq!(parse_count(" 42\n"), Some(42));
+        assert_eq!(parse_count("k"), None);
+        assert_eq!(parse_count("stars"), None);
+    }
+
+    #[test]
+    fn test_reached_max_per_author() {
+        let config = TrendingConfig {
+            max_per_author_per_run: Some(2),
+            ..Default::default()
+        };
+        let mut summary = LoopSummary::default();
+        summary.authors_posted.insert("foo".to_string(), 1);
+        assert!(!summary.reached_max_per_author(&config, "Foo"));
+        summary.authors_posted.insert("foo".to_string(), 2);
+        assert!(summary.reached_max_per_author(&config, "Foo"));
+        assert!(!summary.reached_max_per_author(&config, "bar"));
+        assert!(!summary.reached_max_per_author(&TrendingConfig::default(), "foo"));
+    }
+
+    #[test]
+    fn test_alerts() {
+        let mut alerts = Alerts::default();
+        let now = std::time::Instant::now();
+        assert!(alerts.should_send("loop", now));
+        assert!(!alerts.should_send("loop", now + ALERT_THROTTLE / 2));
+        assert!(alerts.should_send("post:bluesky", now));
+        assert!(alerts.should_send("loop", now + ALERT_THROTTLE));
+
+        assert_eq!(alerts.record_post("bluesky", false), 1);
+        assert_eq!(alerts.record_post("bluesky", false), 2);
+        assert_eq!(alerts.record_post("discord", false), 1);
+        assert_eq!(alerts.record_post("bluesky", true), 0);
+        assert_eq!(alerts.record_post("bluesky", false), 1);
+
+        assert_eq!(
+            alert_message("Failed to post to bluesky 3 times in a row: rejected", Some(reqwest::StatusCode::UNAUTHORIZED)),
+            "Failed to post to bluesky 3 times in a row: rejected (status 401 Unauthorized), see the logs"
+        );
+        assert_eq!(
+            alert_message("Failed to post trending repos", None),
+            "Failed to post trending repos, see the logs"
+        );
+    }
+
+    #[test]
+    fn test_sort_repos() {
+        let repos = vec![
+            repo!("a", "a", "", 2),
+            repo!("b", "b", "", 3),
+            repo!("c", "c", "", 1),
+            repo!("d", "d", "", 3),
+        ];
+        let names = |order| {
+            let mut repos = repos.clone();
+            sort_repos(&mut repos, order);
+            repos.into_iter().map(|repo| repo.name).collect::<String>()
+        };
+        assert_eq!(names(PostOrder::Trending), "abcd");
+        assert_eq!(names(PostOrder::StarsDesc), "bdac");
+        assert_eq!(names(PostOrder::StarsAsc), "cabd");
+        let mut random = names(PostOrder::Random).into_bytes();
+        random.sort_unstable();
+        assert_eq!(random, b"abcd");
+        let mut weighted = names(PostOrder::Weighted).into_bytes();
+        weighted.sort_unstable();
+        assert_eq!(weighted, b"abcd");
+    }
+
+    #[test]
+    fn test_weighted_shuffle() {
+        let repos = vec![
+            repo!("a", "a", "", 0),
+            repo!("b", "b", "", 1000),
+            repo!("c", "c", "", 1),
+        ];
+        let mut rng = StdRng::seed_from_u64(42);
+        let mut first = HashMap::new();
+        for _ in 0..1000 {
+            let mut shuffled = repos.clone();
+            weighted_shuffle(&mut shuffled, |repo| repo.stars, &mut rng);
+            *first.entry(shuffled[0].name.clone()).or_insert(0) += 1;
+        }
+        // Drawn first with a probability of 1000/1001
+        assert!(first["b"] > 980);
+        assert_eq!(first.values().sum::<usize>(), 1000);
+    }
+
+    #[test]
+    fn test_dedup_repos() {
+        let repos = dedup_repos(vec![
+            repo!("foo", "bar", "first", 1),
+            repo!("foo", "baz", "", 2),
+            repo!("foo", "bar", "second", 3),
+        ]);
+        assert_eq!(
+            repos,
+            vec![repo!("foo", "bar", "first", 1), repo!("foo", "baz", "", 2)]
+        );
+    }
+
+    #[test]
+    fn test_parse_trending_messy_title() {
+        let html = "<div class=\"Box\"><article class=\"Box-row\">
+            <h1 class=\"h3\"><a href=\"/foo/bar\">
+                <span>\u{200B}foo\n /</span>\n bar\u{FEFF}\t</a></h1>
+            <p class=\"my-1\"> baz </p>
+        </article></div>";
+        let repos = parse_trending(html.to_string()).unwrap();
+        assert_eq!(repos, vec![repo!("foo", "bar", "baz", 0)]);
+        assert_eq!(repo_uri(&repos[0]), "https://github.com/foo/bar");
+
+        assert_eq!(
+            repo_uri(&repo!("foo", "bar/../baz qux", "", 0)),
+            "https://github.com/foo/bar%2F..%2Fbaz%20qux"
+        );
+    }
+
+    #[test]
+    fn test_parse_trending_empty() {
+        let error = parse_trending("<html><body></body></html>".to_string()).unwrap_err();
+        assert!(matches!(error, FetchError::NoRepos));
+        assert!(!error.is_retryable());
+    }
+
+    #[tokio::test]
+    async fn test_post_error() {
+        use anyhow::Context;
+        use wiremock::matchers::path;
+
+        let server = wiremock::MockServer::start().await;
+        for (status, route) in [(400, "/bad"), (503, "/unavailable")] {
+            wiremock::Mock::given(path(route))
+                .respond_with(wiremock::ResponseTemplate::new(status))
+                .mount(&server)
+                .await;
+        }
+        let post = |route: &str| {
+            let request = reqwest::Client::new().post(format!("{}{}", server.uri(), route));
+            async move {
+                send_with_rate_limit(request)
+                    .await
+                    .context("While posting")
+                    .map(|_| ())
+                    .map_err(PostError::from)
+            }
+        };
+
+        let error = post("/bad").await.unwrap_err();
+        assert_eq!(error.kind(), "rejected");
+        // The URL of webhooks and bots is a secret
+        assert!(!format!("{:#}", error).contains("/bad"));
+        assert!(!error.is_retryable());
+        let error = post("/unavailable").await.unwrap_err();
+        assert_eq!(error.kind(), "other");
+        assert!(error.is_retryable());
+        assert!(PostError::Timeout("twitter").is_retryable());
+
+        let error = twitter_v2::Error::Api(twitter_v2::ApiError {
+            status: reqwest::StatusCode::FORBIDDEN,
+            ..Default::default()
+        });
+        let error = PostError::from(anyhow::Error::new(error).context("While tweeting"));
+        assert_eq!(error.kind(), "rejected");
+        let error = atrium_xrpc::error::Error::<atrium_api::com::atproto::repo::create_record::Error>::XrpcResponse(
+            atrium_xrpc::error::XrpcError {
+                status: reqwest::StatusCode::BAD_REQUEST,
+                error: None,
+            },
+        );
+        assert_eq!(
+            PostError::from(anyhow::Error::new(error)).kind(),
+            "rejected"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_post_error_secret() {
+        let server = wiremock::MockServer::start().await;
+        wiremock::Mock::given(wiremock::matchers::method("POST"))
+            .respond_with(wiremock::ResponseTemplate::new(401))
+            .mount(&server)
+            .await;
+        let client = reqwest::Client::new();
+
+        let telegram = TelegramConfig {
+            bot_token: "123456:telegram-secret".to_string(),
+            chat_id: "@rust_trending".to_string(),
+            denylist: Default::default(),
+            post_interval: None,
+            post_ttl: None,
+        };
+        let error = post_telegram(&client, &server.uri(), &telegram, "content")
+            .await
+            .unwrap_err();
+        assert!(!format!("{:#}", error).contains("telegram-secret"));
+        assert_eq!(PostError::from(error).kind(), "rejected");
+
+        let discord = DiscordConfig {
+            webhook_url: format!("{}/api/webhooks/123/discord-secret", server.uri())
+                .parse()
+                .unwrap(),
+            denylist: Default::default(),
+            post_interval: None,
+            post_ttl: None,
+        };
+        let error = post_discord(
+            &client,
+            &discord,
+            &repo!("a", "b", "c", 1),
+            &Default::default(),
+        )
+        .await
+        .unwrap_err();
+        assert!(!format!("{:#}", error).contains("discord-secret"));
+
+        let slack = SlackConfig {
+            webhook_url: format!("{}/services/T000/B000/slack-secret", server.uri())
+                .parse()
+                .unwrap(),
+            denylist: Default::default(),
+            post_interval: None,
+            post_ttl: None,
+        };
+        let error = post_slack(
+            &client,
+            &slack,
+            &repo!("a", "b", "c", 1),
+            &Default::default(),
+        )
+        .await
+        .unwrap_err();
+        assert!(!format!("{:#}", error).contains("slack-secret"));
+    }
+
+    #[test]
+    fn test_search_repos() {
+        assert_eq!(
+            search_query(
+                "rust",
+                Since::Weekly,
+                time::macros::datetime!(2023-07-03 12:00 UTC)
+            ),
+            "language:rust created:>2023-06-26"
+        );
+
+        let output: SearchRepositoriesOutput = serde_json::from_str(
+            r#"{
+                "total_count": 1,
+                "items": [
+                    {
+                        "name": "rust",
+                        "owner": { "login": "rust-lang" },
+                        "description": null,
+                        "stargazers_count": 49626,
+                        "forks_count": 12345,
+                        "language": "Rust",
+                        "created_at": "2010-06-16T20:39:03Z",
+                        "topics": ["compiler", "rust"]
+                    }
+                ]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            output.items.into_iter().map(Repo::from).collect::<Vec<_>>(),
+            vec![repo!(
+                "rust-lang",
+                "rust",
+                "",
+                49626,
+                forks: 12345,
+                language: Some("Rust".to_string()),
+                created_at: Some(time::macros::datetime!(2010-06-16 20:39:03 UTC)),
+                topics: vec!["compiler".to_string(), "rust".to_string()]
+            )]
+        );
+    }
+
+    #[test]
+    fn test_make_tweet() {
+        assert_eq!(
+            make_tweet(&repo!(
+                "wez",
+                "wezterm",
+                "A GPU-accelerated cross-platform terminal emulator and multiplexer written by @wez and implemented in Rust",
+                5924
+            ), DEFAULT_TEMPLATE, "", &FormatConfig::default()),
+            "wez / wezterm: A GPU-accelerated cross-platform terminal emulator and multiplexer written by ﹫wez and implemented in Rust ★5924 https://github.com/wez/wezterm"
+        );
+        assert_eq!(
+            make_tweet(&repo!(
+                "AlfioEmanueleFresta",
+                "xdg-credentials-portal",
+                "FIDO2 (WebAuthn) and FIDO U2F platform library for Linux written in Rust; includes a proposal for a new D-Bus Portal interface for FIDO2, accessible from Flatpak apps and Snaps key",
+                192
+            ), DEFAULT_TEMPLATE, "", &FormatConfig::default()),
+            "AlfioEmanueleFresta / xdg-credentials-portal: FIDO2 (WebAuthn) and FIDO U2F platform library for Linux written in Rust; includes a proposal for a new D-Bus Portal interface for FIDO2, accessible from Flatpak ... ★192 https://github.com/AlfioEmanueleFresta/xdg-credentials-portal"
+        );
+        assert_eq!(
+            make_tweet(&repo!(
+                "meilisearch",
+                "meilisearch",
+                "A lightning-fast search engine that fits effortlessly into your apps, websites, and workflow.",
+                30388
+            ), DEFAULT_TEMPLATE, "", &FormatConfig::default()),
+            "meilisearch: A lightning-fast search engine that fits effortlessly into your apps, websites, and workflow. ★30388 https://github.com/meilisearch/meilisearch"
+        );
+        assert_eq!(
+            make_tweet(&repo!(
+                "AlfioEmanueleFresta",
+                "xdg-credentials-portal",
+                "FIDO2 (WebAuthn) and FIDO U2F platform library for Linux written in Rust; includes a proposal for a new D-Bus Portal interface for FIDO2, accessible from Flatpak apps and Snaps key",
+                192
+            ), default_template(false), "", &FormatConfig::default()),
+            "AlfioEmanueleFresta / xdg-credentials-portal: FIDO2 (WebAuthn) and FIDO U2F platform library for Linux written in Rust; includes a proposal for a new D-Bus Portal interface for FIDO2, accessible from Flatpak apps a ... https://github.com/AlfioEmanueleFresta/xdg-credentials-portal"
+        );
+    }
+
+    #[test]
+    fn test_make_post_extras() {
+        let repo = repo!("foo", "bar", "A test repo", 1234, forks: 5678, stars_period: 239, period: Some(Since::Daily), language: Some("C++".to_string()));
+        let all = PostExtras {
+            show_forks: true,
+            show_language: true,
+            show_period_stars: true,
+        };
+        assert_eq!(
+            make_post_extras(&repo, &PostExtras::default(), &FormatConfig::default()),
+            ""
+        );
+        assert_eq!(
+            make_post_extras(&repo, &all, &FormatConfig::default()),
+            " ⑂5678 #CPlusPlus (+239 today)"
+        );
+        assert_eq!(
+            make_tweet(
+                &repo,
+                DEFAULT_TEMPLATE,
+                &format!("{} #rust", make_post_extras(&repo, &all, &FormatConfig::default())),
+                &FormatConfig::default()
+            ),
+            "foo / bar: A test repo ★1234 https://github.com/foo/bar ⑂5678 #CPlusPlus (+239 today) #rust"
+        );
+
+        // Left out when unknown, e.g. fetched with the GitHub API
+        let repo = repo!("foo", "bar", "A test repo", 1234);
+        assert_eq!(
+            make_post_extras(&repo, &all, &FormatConfig::default()),
+            " ⑂0"
+        );
+
+        let config: MastodonConfig = toml::from_str(
+            "instance_url = \"https://mastodon.social\"\ntoken = \"\"\nextras = { show_forks = true }",
+        )
+        .unwrap();
+        assert!(config.extras.show_forks);
+        assert!(!config.extras.show_language);
+    }
+
+    #[test]
+    fn test_jittered() {
+        assert_eq!(jittered(60, 0), Duration::from_secs(60));
+        for _ in 0..100 {
+            let delay = jittered(60, 10);
+            assert!(delay >= Duration::from_secs(60) && delay <= Duration::from_secs(70));
+        }
+    }
+
+    #[test]
+    fn test_make_digest() {
+        let repos = vec![
+            repo!("foo", "bar", "", 1234),
+            repo!("@baz", "qux", "", 56),
+            repo!("quux", "quux", "", 7),
+        ];
+        assert_eq!(
+            make_digest(
+                "rust",
+                &repos,
+                &FormatConfig::default(),
+                TOOT_LENGTH,
+                LengthUnit::Chars,
+                Some(MASTODON_FIXED_URL_LENGTH)
+            ),
+            "Top 3 trending Rust repos today:\n\
+             1. foo / bar ★1234 https://github.com/foo/bar\n\
+             2. ﹫baz / qux ★56 https://github.com/@baz/qux\n\
+             3. quux ★7 https://github.com/quux/quux"
+        );
+        assert_eq!(
+            make_digest(
+                "rust",
+                &repos,
+                &FormatConfig::default(),
+                120,
+                LengthUnit::Chars,
+                Some(MASTODON_FIXED_URL_LENGTH)
+            ),
+            "Top 2 trending Rust repos today:\n\
+             1. foo / bar ★1234 https://github.com/foo/bar\n\
+             2. ﹫baz / qux ★56 https://github.com/@baz/qux"
+        );
+        // The first repo is kept even if it doesn't fit
+        assert_eq!(
+            make_digest(
+                "go",
+                &repos,
+                &FormatConfig::default(),
+                10,
+                LengthUnit::Bytes,
+                None
+            ),
+            "Top 1 trending Go repos today:\n1. foo / bar ★1234 https://github.com/foo/bar"
+        );
+    }
+
+    #[test]
+    fn test_trending_config() {
+        let config: TrendingConfig = toml::from_str(r#"since = ["daily", "weekly"]"#).unwrap();
+        assert_eq!(config.languages, vec!["rust".to_string()]);
+        assert_eq!(config.since, vec![Since::Daily, Since::Weekly]);
+        assert!(toml::from_str::<TrendingConfig>(r#"since = ["yearly"]"#).is_err());
+
+        let github = GithubConfig::default();
+        let url = |host: &Url, template: &str, language: &str, since: Since| {
+            trending_url(host, template, language, since)
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(
+            url(
+                &github.host(),
+                github.trending_path_template(),
+                "rust",
+                Since::Monthly
+            ),
+            "https://github.com/trending/rust?since=monthly"
+        );
+        assert_eq!(
+            url(
+                &github.host(),
+                github.trending_path_template(),
+                "c#",
+                Since::Daily
+            ),
+            "https://github.com/trending/c%23?since=daily"
+        );
+        assert_eq!(github.api_url().as_str(), "https://api.github.com/");
+
+        let github: GithubConfig = toml::from_str(
+            r#"
+            host = "https://github.example.com/"
+            trending_path_template = "/explore/trending/{language}/{since}"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            url(
+                &github.host(),
+                github.trending_path_template(),
+                "c++",
+                Since::Weekly
+            ),
+            "https://github.example.com/explore/trending/c%2B%2B/weekly"
+        );
+        assert_eq!(
+            github.api_url().as_str(),
+            "https://github.example.com/api/v3"
+        );
+
+        let repo = repo!("foo", "bar", "", 0);
+        assert_eq!(
+            repo_key(&key_prefix("rust", Since::Daily), &repo),
+            "foo/bar"
+        );
+        assert_eq!(
+            repo_key(&key_prefix("rust", Since::Weekly), &repo),
+            "weekly:foo/bar"
+        );
+        assert_eq!(
+            repo_key(&key_prefix("Go", Since::Monthly), &repo),
+            "go:monthly:foo/bar"
+        );
+    }
+
+    #[test]
+    fn test_make_telegram_message() {
+        assert_eq!(
+            make_telegram_message(&repo!(
+                "foo",
+                "bar",
+                "A <fast> & safe library by @foo",
+                42
+            ), &FormatConfig::default()),
+            "<a href=\"https://github.com/foo/bar\">foo / bar</a>: A &lt;fast&gt; &amp; safe library by @foo ★42"
+        );
+    }
+
+    #[test]
+    fn test_append_feed() {
+        let dir = std::env::temp_dir().join(format!("rust-trending-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let config = FeedConfig {
+            output_path: dir.join("feed.xml"),
+            max_items: 2,
+        };
+
+        append_feed(&config, &repo!("foo", "bar", "first", 0)).unwrap();
+        append_feed(&config, &repo!("foo", "baz", "second", 0)).unwrap();
+        append_feed(&config, &repo!("foo", "foo", "third", 0)).unwrap();
+
+        let file = std::fs::File::open(&config.output_path).unwrap();
+        let feed = atom_syndication::Feed::read_from(std::io::BufReader::new(file)).unwrap();
+        let ids = feed
+            .entries
+            .iter()
+            .map(|e| e.id.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ids,
+            vec!["https://github.com/foo/foo", "https://github.com/foo/baz"]
+        );
+        assert_eq!(feed.entries[0].title.as_str(), "foo");
+        assert_eq!(feed.entries[0].links[0].href, "https://github.com/foo/foo");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_long_title_within_length() {
+        let repo = repo!("a".repeat(200), "bar", "some description", 123);
+        let tweet = make_tweet(&repo, DEFAULT_TEMPLATE, "", &FormatConfig::default());
+        assert!(tweet.len() <= TWEET_LENGTH);
+        assert!(tweet.contains("aaa... ★123 https://github.com/aaa"));
+        let post = make_bluesky_post(&repo, DEFAULT_TEMPLATE, "", &FormatConfig::default());
+        assert!(post.graphemes(true).count() <= BLUESKY_POST_LENGTH);
+        assert!(post.contains("aaa... ★123 https://github.com/aaa"));
+
+        let repo = repo!("a".repeat(600), "bar", "some description", 123);
+        let toot = make_toot(&repo, DEFAULT_TEMPLATE, "", None, &FormatConfig::default());
+        let url = repo_uri(&repo);
+        assert!(
+            toot.chars().count() - url.chars().count() + MASTODON_FIXED_URL_LENGTH <= TOOT_LENGTH
+        );
+        assert!(toot.contains("aaa... ★123 https://github.com/aaa"));
+    }
+
+    #[test]
+    fn test_make_bluesky_post_counts_graphemes() {
+        let repo = repo!(
+            "rust-lang",
+            "rust",
+            "🦀 Empowering everyone to build reliable and efficient software. ".repeat(10),
+            49626
+        );
+        let post = make_bluesky_post(&repo, DEFAULT_TEMPLATE, "", &FormatConfig::default());
+        assert_eq!(post.graphemes(true).count(), BLUESKY_POST_LENGTH);
+        assert!(post.starts_with("rust-lang / rust: 🦀 Empowering"));
+        assert!(post.ends_with(" ... ★49626 https://github.com/rust-lang/rust"));
+    }
+
+    #[test]
+    fn test_make_bluesky_link_facet() {
+        let repo = repo!("foo", "bar", "러스트로 작성된 🦀 라이브러리", 42);
+        let text = make_bluesky_post(&repo, DEFAULT_TEMPLATE, "", &FormatConfig::default());
+        let uri = "https://github.com/foo/bar";
+        let facet = make_bluesky_link_facet(&text, uri).unwrap();
+        let start = facet.index.byte_start as usize;
+        let end = facet.index.byte_end as usize;
+        assert_eq!(start, "foo / bar: 러스트로 작성된 🦀 라이브러리 ★42 ".len());
+        assert_eq!(&text[start..end], uri);
+        assert!(make_bluesky_link_facet("no link", uri).is_none());
+    }
+
+    #[test]
+    fn test_make_bluesky_record_without_thumb() {
+        let config: BlueskyConfig = toml::from_str(
+            r#"
+            host = "https://bsky.social"
+            identifier = "foo.bsky.social"
+            password = "password"
+
+            [denylist]
+            "#,
+        )
+        .unwrap();
+        assert!(config.embed_image);
+        let repo = repo!("foo", "bar", "baz", 42);
+        let text = make_bluesky_post(&repo, DEFAULT_TEMPLATE, "", &FormatConfig::default());
+        let record = make_bluesky_record(
+            &config,
+            &repo,
+            &FormatConfig::default(),
+            text,
+            None,
+            "2023-07-03T12:00:00Z".to_string(),
+        );
+        let record = serde_json::to_value(record).unwrap();
+        let external = &record["embed"]["external"];
+        assert_eq!(external["uri"], "https://github.com/foo/bar");
+        assert_eq!(external["title"], "foo / bar");
+        assert!(external.get("thumb").is_none());
+        assert_eq!(record["facets"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_make_bluesky_post_input() {
+        let config = |extra: &str| -> BlueskyConfig {
+            toml::from_str(&format!(
+                r#"
+                host = "https://bsky.social"
+                identifier = "foo.bsky.social"
+                password = "password"
+                {}
+                "#,
+                extra
+            ))
+            .unwrap()
+        };
+        let repo = repo!("foo", "bar", "baz", 42);
+        let record = |config: &BlueskyConfig| {
+            make_bluesky_record(
+                config,
+                &repo,
+                &FormatConfig::default(),
+                "foo / bar: baz".to_string(),
+                None,
+                "2023-07-03T12:00:00Z".to_string(),
+            )
+        };
+
+        let default = config("");
+        assert!(!default.disable_replies);
+        let input = make_bluesky_post_input(&default, "did:plc:foo", record(&default)).unwrap();
+        assert_eq!(input["repo"], "did:plc:foo");
+        assert_eq!(input["record"]["$type"], "app.bsky.feed.post");
+        assert!(input["record"].get("labels").is_none());
+
+        let labeled = config(r#"labels = ["graphic-media"]"#);
+        let input = make_bluesky_post_input(&labeled, "did:plc:foo", record(&labeled)).unwrap();
+        assert_eq!(
+            input["record"]["labels"],
+            serde_json::json!({
+                "$type": "com.atproto.label.defs#selfLabels",
+                "values": [{"val": "graphic-media"}],
+            })
+        );
+
+        let input = make_bluesky_threadgate_input(
+            "did:plc:foo",
+            "at://did:plc:foo/app.bsky.feed.post/3k2a4b",
+            "2023-07-03T12:00:00Z",
+        );
+        assert_eq!(input["collection"], "app.bsky.feed.threadgate");
+        assert_eq!(input["rkey"], "3k2a4b");
+        assert_eq!(
+            input["record"]["post"],
+            "at://did:plc:foo/app.bsky.feed.post/3k2a4b"
+        );
+        assert_eq!(input["record"]["allow"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn test_make_toot_counts_chars() {
+        let repo = repo!("러스트", "트렌딩", "가".repeat(600), 123);
+        let toot = make_toot(&repo, DEFAULT_TEMPLATE, "", None, &FormatConfig::default());
+        let url = repo_uri(&repo);
+        let length = toot.chars().count() - url.chars().count() + MASTODON_FIXED_URL_LENGTH;
+        assert!(length <= TOOT_LENGTH);
+        // Counting bytes would have truncated the description to a third
+        assert!(length >= TOOT_LENGTH - 1);
+        assert!(toot.starts_with("러스트 / 트렌딩: 가가가"));
+        // Names are percent-encoded, which Mastodon counts as a fixed length anyway
+        assert!(toot.ends_with(&format!(" ... ★123 {}", url)));
+
+        let content_warning = "자동 게시물";
+        let toot = make_toot(
+            &repo,
+            DEFAULT_TEMPLATE,
+            "",
+            Some(content_warning),
+            &FormatConfig::default(),
+        );
+        let length = toot.chars().count() - url.chars().count()
+            + MASTODON_FIXED_URL_LENGTH
+            + content_warning.chars().count();
+        assert!(length <= TOOT_LENGTH);
+        assert!(length >= TOOT_LENGTH - 1);
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        let now = time::macros::datetime!(2015-10-21 07:27:00 UTC);
+        assert_eq!(
+            parse_retry_after("120", now),
+            Some(std::time::Duration::from_secs(120))
+        );
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT", now),
+            Some(std::time::Duration::from_secs(60))
+        );
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:26:00 GMT", now),
+            None
+        );
+        assert_eq!(parse_retry_after("soon", now), None);
+    }
+
+    #[test]
+    fn test_is_healthy() {
+        assert!(is_healthy(1000, 1000, 60));
+        assert!(is_healthy(1000, 1120, 60));
+        assert!(!is_healthy(1000, 1121, 60));
+        assert!(is_healthy(1000, 900, 60));
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_store() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        let mut store = SqliteStore::new(conn).unwrap();
+
+        assert!(!store.is_posted("foo/bar").await.unwrap());
+        store.mark_posted("foo/bar", "{}", 3600).await.unwrap();
+        assert!(store.is_posted("foo/bar").await.unwrap());
+        assert_eq!(
+            store.posted_value("foo/bar").await.unwrap().as_deref(),
+            Some("{}")
+        );
+        assert!(!store.is_posted("go:foo/bar").await.unwrap());
+
+        // Expired keys are not posted
+        store.mark_posted("foo/baz", "{}", 0).await.unwrap();
+        assert!(!store.is_posted("foo/baz").await.unwrap());
+        assert_eq!(store.posted_value("foo/baz").await.unwrap(), None);
+
+        store.set_heartbeat("rt:last_fetch", 1).await.unwrap();
+        store.set_heartbeat("rt:last_fetch", 2).await.unwrap();
+        let timestamp: i64 = store
+            .conn
+            .query_row(
+                "SELECT timestamp FROM heartbeats WHERE key = 'rt:last_fetch'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(timestamp, 2);
+    }
+
+    #[test]
+    fn test_repo_key() {
+        assert_eq!(repo_key("", &repo!("Foo", "Bar", "", 0)), "foo/bar");
+        assert_eq!(
+            repo_key("", &repo!("Foo", "Bar", "", 0)),
+            repo_key("", &repo!("foo", "bar", "", 0))
+        );
+        assert_eq!(
+            repo_key(
+                &key_prefix("Go", Since::Weekly),
+                &repo!("Foo", "Bar", "", 0)
+            ),
+            "go:weekly:foo/bar"
+        );
+        assert_eq!(
+            platform_key("mastodon", "", &repo!("Foo", "Bar", "", 0)),
+            "mastodon:foo/bar"
+        );
+    }
+
+    #[test]
+    fn test_platform_key() {
+        let repo = repo!("foo", "bar", "", 0);
+        assert_eq!(platform_key("mastodon", "", &repo), "mastodon:foo/bar");
+        assert_eq!(
+            platform_key("bluesky", &key_prefix("go", Since::Weekly), &repo),
+            "bluesky:go:weekly:foo/bar"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_flag() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        let mut store = SqliteStore::new(conn).unwrap();
+
+        assert_eq!(store.flag("rt:paused").await.unwrap(), None);
+        store
+            .conn
+            .execute(
+                "INSERT INTO flags (key, value) VALUES ('rt:paused', '1')",
+                [],
+            )
+            .unwrap();
+        assert_eq!(store.flag("rt:paused").await.unwrap().as_deref(), Some("1"));
+
+        assert!(is_truthy("1"));
+        assert!(is_truthy("true"));
+        assert!(is_truthy("incident #42"));
+        assert!(!is_truthy(""));
+        assert!(!is_truthy("0"));
+        assert!(!is_truthy("False"));
+        assert!(!is_truthy("off"));
+    }
+
+    #[tokio::test]
+    async fn test_deadletters() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        let mut store = SqliteStore::new(conn).unwrap();
+
+        assert!(store.set_deadletter("a", "1", 2).await.unwrap());
+        assert!(store.set_deadletter("b", "2", 2).await.unwrap());
+        // A new key is dropped beyond the length, but an existing one is replaced
+        assert!(!store.set_deadletter("c", "3", 2).await.unwrap());
+        assert!(store.set_deadletter("a", "4", 2).await.unwrap());
+        assert_eq!(store.deadletter("a").await.unwrap().as_deref(), Some("4"));
+        store.remove_deadletter("b").await.unwrap();
+        assert_eq!(
+            store.deadletters().await.unwrap(),
+            vec![("a".to_string(), "4".to_string())]
+        );
+        store.remove_deadletter("a").await.unwrap();
+
+        let mut repo = repo!("foo", "bar", "", 42);
+        repo.period = Some(Since::Weekly);
+        repo.created_at = Some(time::macros::datetime!(2023-01-01 00:00 UTC));
+        let failures = || vec![("bluesky".to_string(), "Timed out".to_string())];
+        let mut summary = LoopSummary {
+            failures: failures(),
+            ..Default::default()
+        };
+        deadletter_failures(&mut store, "go:", &repo, false, &mut summary).await;
+        assert!(summary.failures.is_empty());
+        let values = store.deadletters().await.unwrap();
+        assert_eq!(values.len(), 1);
+        assert_eq!(values[0].0, "bluesky:go:foo/bar");
+        let deadletter: DeadLetter = serde_json::from_str(&values[0].1).unwrap();
+        assert_eq!(deadletter.prefix, "go:");
+        assert_eq!(deadletter.platform, "bluesky");
+        assert_eq!(deadletter.error, "Timed out");
+        assert_eq!(deadletter.attempts, 0);
+        assert_eq!(deadletter.into_repo(), repo);
+
+        // Failing again in a loop keeps the attempts, and only retries count
+        summary.failures = failures();
+        deadletter_failures(&mut store, "go:", &repo, false, &mut summary).await;
+        summary.failures = failures();
+        deadletter_failures(&mut store, "go:", &repo, true, &mut summary).await;
+        let values = store.deadletters().await.unwrap();
+        assert_eq!(values.len(), 1);
+        let deadletter: DeadLetter = serde_json::from_str(&values[0].1).unwrap();
+        assert_eq!(deadletter.attempts, 1);
+
+        // Given up after the last retry
+        for _ in 1..DEADLETTER_MAX_ATTEMPTS {
+            summary.failures = failures();
+            deadletter_failures(&mut store, "go:", &repo, true, &mut summary).await;
+        }
+        assert!(store.deadletters().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_posted_record() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        let mut store = SqliteStore::new(conn).unwrap();
+        let repo = repo!("foo", "bar", "", 42);
+
+        assert_eq!(
+            get_posted_record(&mut store, "", &repo).await.unwrap(),
+            None
+        );
+        mark_posted_repo(&mut store, "", &repo, vec!["twitter".to_string()], 3600)
+            .await
+            .unwrap();
+        let record = get_posted_record(&mut store, "", &repo)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(record.stars_at_post, 42);
+        assert_eq!(record.platforms, vec!["twitter".to_string()]);
+
+        // Keys of older versions only hold the timestamp
+        store
+            .mark_posted("foo/baz", "1700000000", 3600)
+            .await
+            .unwrap();
+        let repo = repo!("foo", "baz", "", 0);
+        assert_eq!(
+            get_posted_record(&mut store, "", &repo).await.unwrap(),
+            None
+        );
+
+        // Keys were written as displayed before they were lowercased
+        let repo = repo!("Foo", "Qux", "", 42);
+        mark_posted_key(
+            &mut store,
+            "mastodon:go:Foo/Qux",
+            &repo,
+            vec!["mastodon".to_string()],
+            3600,
+        )
+        .await
+        .unwrap();
+        let key = platform_key("mastodon", "go:", &repo);
+        assert_eq!(key, "mastodon:go:foo/qux");
+        assert!(is_key_posted(&mut store, &key, &repo).await.unwrap());
+        assert!(get_posted_key_record(&mut store, &key, &repo)
+            .await
+            .unwrap()
+            .is_some());
+        assert_eq!(cased_key("go:foo/bar", &repo!("foo", "bar", "", 0)), None);
+    }
+
+    #[test]
+    fn test_repost_threshold() {
+        let record = PostedRecord {
+            posted_at: 0,
+            stars_at_post: 100,
+            platforms: Vec::new(),
+        };
+        assert_eq!(
+            repost_threshold(&record, &repo!("foo", "bar", "", 149), 50),
+            None
+        );
+        assert_eq!(
+            repost_threshold(&record, &repo!("foo", "bar", "", 150), 50),
+            Some(150)
+        );
+    }
+
+    #[test]
+    fn test_render_post() {
+        let repo = repo!(
+            "foo",
+            "bar",
+            "a".repeat(100),
+            42,
+            forks: 7,
+            language: Some("Rust".to_string())
+        );
+        assert_eq!(
+            render_post(
+                "{name} by {author} ({forks} forks) {url} #{language}",
+                &repo,
+                &FormatConfig::default(),
+                100,
+                LengthUnit::Bytes,
+                None,
+                "",
+                false
+            ),
+            "bar by foo (7 forks) https://github.com/foo/bar #Rust"
+        );
+        assert_eq!(
+            render_post(
+                "{title} {unknown} {",
+                &repo,
+                &FormatConfig::default(),
+                100,
+                LengthUnit::Bytes,
+                None,
+                "",
+                false
+            ),
+            "foo / bar {unknown} {"
+        );
+
+        // Only the description is truncated
+        let post = render_post(
+            "{description}\n{url}",
+            &repo,
+            &FormatConfig::default(),
+            50,
+            LengthUnit::Bytes,
+            None,
+            "",
+            false,
+        );
+        assert_eq!(post.len(), 50);
+        assert!(post.ends_with(" ...\nhttps://github.com/foo/bar"));
+
+        // Placeholders in values are not expanded
+        let repo = repo!("foo", "bar", "{url}", 42);
+        assert_eq!(
+            render_post(
+                "{description}",
+                &repo,
+                &FormatConfig::default(),
+                100,
+                LengthUnit::Bytes,
+                None,
+                "",
+                false
+            ),
+            "{url}"
+        );
+
+        // No bare `#` without a language
+        let repo = repo!("foo", "bar", "", 42);
+        let render = |template| {
+            render_post(
+                template,
+                &repo,
+                &FormatConfig::default(),
+                100,
+                LengthUnit::Bytes,
+                None,
+                "",
+                false,
+            )
+        };
+        assert_eq!(render("{url} #{language}"), "https://github.com/foo/bar");
+        assert_eq!(render("{name} #{language} by {author}"), "bar by foo");
+    }
+
+    #[test]
+    fn test_make_hashtags() {
+        let hashtags = ["rustlang", "#opensource", "#rustlang", " "].map(String::from);
+        assert_eq!(make_hashtags(&hashtags), " #rustlang #opensource");
+        assert_eq!(make_hashtags(&[]), "");
+
+        let repo = repo!("foo", "bar", "a".repeat(500), 42);
+        let toot = make_toot(
+            &repo,
+            DEFAULT_TEMPLATE,
+            " #rustlang",
+            None,
+            &FormatConfig::default(),
+        );
+        assert!(toot.ends_with(" ... ★42 https://github.com/foo/bar #rustlang"));
+        let url = repo_uri(&repo);
+        assert!(
+            toot.chars().count() - url.chars().count() + MASTODON_FIXED_URL_LENGTH <= TOOT_LENGTH
+        );
+    }
+
+    #[test]
+    fn test_make_idempotency_key() {
+        let instance_url = url::Url::parse("https://mastodon.social").unwrap();
+        let repo = repo!("foo", "bar", "", 42);
+        let date = time::macros::date!(2023 - 07 - 01);
+        let key = make_idempotency_key(&instance_url, &repo, date);
+        assert_eq!(key, make_idempotency_key(&instance_url, &repo, date));
+
+        let other_instance = url::Url::parse("https://fosstodon.org").unwrap();
+        assert_ne!(key, make_idempotency_key(&other_instance, &repo, date));
+        let next_date = time::macros::date!(2023 - 07 - 02);
+        assert_ne!(key, make_idempotency_key(&instance_url, &repo, next_date));
+    }
+
+    #[test]
+    fn test_args() {
+        use clap::{CommandFactory, Parser};
+
+        Args::command().debug_assert();
+
+        let args = Args::parse_from(["rust-trending", "--once", "config.toml"]);
+        assert!(args.once);
+        assert_eq!(args.config_path, Some("config.toml".into()));
+        let args = Args::parse_from(["rust-trending", "--config", "config.toml"]);
+        assert_eq!(args.config, std::path::PathBuf::from("config.toml"));
+        assert_eq!(args.config_path, None);
+
+        let args = Args::parse_from(["rust-trending", "--post", "foo/bar", "--force"]);
+        assert_eq!(args.post.as_deref(), Some("foo/bar"));
+        assert!(args.force);
+        assert!(Args::try_parse_from(["rust-trending", "--force"]).is_err());
+        assert!(Args::parse_from(["rust-trending", "--migrate"]).migrate);
+        assert!(Args::try_parse_from(["rust-trending", "--migrate", "--once"]).is_err());
+    }
+
+    #[test]
+    fn test_validate_config() {
+        let config: Config = toml::from_str(
+            r#"
+            [interval]
+            post_ttl = 604800
+            fetch_interval = 60
+            post_interval = 60
+
+            [redis]
+            url = "redis://localhost:6379/0"
+
+            [mastodon]
+            instance_url = "https://mastodon.social"
+            access_token = "token"
+
+            [denylist]
+            "#,
+        )
+        .unwrap();
+        assert!(config.validate().is_ok());
+
+        let config: Config = toml::from_str(
+            r#"
+            proxy = "ftp://proxy"
+
+            [interval]
+            post_ttl = 604800
+            fetch_interval = 0
+            post_interval = 60
+
+            [mastodon]
+            instance_url = "mailto:foo@example.com"
+            access_token = ""
+
+            [discord_bot]
+            channel_id = 0
+
+            [github]
+            host = "ssh://github.example.com"
+
+            [denylist]
+            "#,
+        )
+        .unwrap();
+        let error = config.validate().unwrap_err().to_string();
+        assert!(error.contains("fetch_interval"));
+        assert!(error.contains("redis or sqlite"));
+        assert!(error.contains("instance_url"));
+        assert!(error.contains("access_token"));
+        assert!(error.contains("proxy"));
+        assert!(!error.contains("nostr"));
+        assert!(error.contains("discord_bot.token"));
+        assert!(error.contains("discord_bot.channel_id"));
+        assert!(error.contains("github.host"));
+
+        let config: Config = toml::from_str(
+            r#"
+            [interval]
+            post_ttl = 604800
+            fetch_interval = 60
+            post_interval = 60
+
+            [redis]
+            url = "redis://localhost:6379/0"
+
+            [denylist]
+            "#,
+        )
+        .unwrap();
+        let error = config.validate().unwrap_err().to_string();
+        assert!(error.contains("At least one platform or feed"));
+
+        let config: Config = toml::from_str(
+            r#"
+            proxy = "socks5://127.0.0.1:1080"
+
+            [interval]
+            post_ttl = 604800
+            fetch_interval = 60
+            post_interval = 60
+
+            [redis]
+            url = "redis://localhost:6379/0"
+
+            [nostr]
+            secret_key = "0000000000000000000000000000000000000000000000000000000000000001"
+            relays = ["wss://relay.damus.io"]
+
+            [denylist]
+            "#,
+        )
+        .unwrap();
+        let error = config.validate().unwrap_err().to_string();
+        assert!(error.contains("proxy can't be used with nostr"));
+    }
+
+    #[test]
+    fn test_parse_config() {
+        let (config, unknown_keys) = parse_config(
+            r#"
+            fetch_intervall = 60
+
+            [interval]
+            post_ttl = 604800
+            fetch_interval = 60
+            post_interval = 60
+
+            [redis]
+            url = "redis://:secret@localhost:6379/0"
+
+            [[mastodon]]
+            instance_url = "https://mastodon.social"
+            access_token = "secret"
+            templte = "{title}"
+
+            [twitter]
+            hashtag = ["rust"]
+
+            [twitter.extras]
+            show_forks = true
+            show_fork = true
+
+            [mastdon]
+            instance_url = "https://fosstodon.org"
+
+            [denylist]
+            names = ["foo"]
+            author_patterns = ["^bar"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            unknown_keys,
+            vec![
+                "fetch_intervall",
+                "mastodon.0.templte",
+                "twitter.hashtag",
+                "twitter.extras.show_fork",
+                "mastdon"
+            ]
+        );
+        assert!(config.twitter.as_ref().unwrap().extras.show_forks);
+
+        let description = config.describe();
+        assert!(!description.contains("secret"));
+        assert!(description.contains("redis://:redacted@localhost:6379/0"));
+        assert!(description.contains("denylist: 2 entries"));
+        assert!(description.contains("platform mastodon:mastodon.social: 0 denylist entries"));
+        assert!(description.contains("platform twitter"));
+    }
+
+    #[test]
+    fn test_post_ttl() {
+        let config: Config = toml::from_str(
+            r#"
+            [interval]
+            post_ttl = 604800
+            fetch_interval = 60
+            post_interval = 60
+
+            [mastodon]
+            instance_url = "https://mastodon.social"
+            access_token = "token"
+            post_ttl = 2592000
+
+            [discord]
+            webhook_url = "https://discord.com/api/webhooks/0/token"
+
+            [denylist]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.post_ttl("mastodon:mastodon.social"), 2592000);
+        assert_eq!(config.post_ttl("discord"), 604800);
+        assert_eq!(config.min_post_ttl(), 604800);
+
+        let config: Config = toml::from_str(
+            r#"
+            [interval]
+            post_ttl = 604800
+            fetch_interval = 60
+            post_interval = 60
+
+            [redis]
+            url = "redis://localhost:6379/0"
+
+            [bluesky]
+            host = "https://bsky.social"
+            identifier = "alice.bsky.social"
+            password = "password"
+            post_ttl = 0
+
+            [denylist]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.min_post_ttl(), 0);
+        let error = config.validate().unwrap_err().to_string();
+        assert!(error.contains("post_ttl of bluesky must not be zero"));
+    }
+
+    #[test]
+    fn test_apply_env_overrides() {
+        let mut config: Config = toml::from_str(
+            r#"
+            [interval]
+            post_ttl = 604800
+            fetch_interval = 60
+            post_interval = 60
+
+            [[mastodon]]
+            instance_url = "https://mastodon.social"
+
+            [[mastodon]]
+            instance_url = "https://fosstodon.org"
+            access_token = "from file"
+
+            [denylist]
+            "#,
+        )
+        .unwrap();
+        assert!(config.validate().is_err());
+
+        config
+            .apply_env_overrides(|name| match name {
+                "RT_REDIS_URL" => Some("redis://localhost:6379/0".to_string()),
+                "RT_MASTODON_ACCESS_TOKEN" => Some("first".to_string()),
+                "RT_MASTODON_1_ACCESS_TOKEN" => Some("second".to_string()),
+                "RT_LOG_FORMAT" => Some("json".to_string()),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(
+            config.redis.as_ref().unwrap().url,
+            "redis://localhost:6379/0"
+        );
+        assert_eq!(config.mastodon[0].access_token, "first");
+        assert_eq!(config.mastodon[1].access_token, "second");
+        assert_eq!(config.log_format, LogFormat::Json);
+        assert!(config.validate().is_ok());
+
+        assert!(config
+            .apply_env_overrides(|name| match name {
+                "RT_LOG_FORMAT" => Some("yaml".to_string()),
+                _ => None,
+            })
+            .is_err());
+    }
+
+    #[test]
+    fn test_legacy_repo_key() {
+        assert_eq!(legacy_repo_key("", "foo/bar"), Some("foo/bar"));
+        assert_eq!(legacy_repo_key("rt:", "rt:foo/bar"), Some("foo/bar"));
+        assert_eq!(legacy_repo_key("rt:", "foo/bar"), None);
+        assert_eq!(legacy_repo_key("", "mastodon:foo/bar"), None);
+        assert_eq!(legacy_repo_key("", "go:weekly:foo/bar"), None);
+        assert_eq!(legacy_repo_key("", "foo/"), None);
+        assert_eq!(legacy_repo_key("", "foo/bar/baz"), None);
+
+        assert!(is_legacy_posted_value("1700000000"));
+        assert!(!is_legacy_posted_value(
+            r#"{"posted_at":1700000000,"stars_at_post":42,"platforms":["twitter"]}"#
+        ));
+    }
+
+    #[test]
+    fn test_platform_names() {
+        let config: Config = toml::from_str(
+            r#"
+            [interval]
+            post_ttl = 604800
+            fetch_interval = 60
+            post_interval = 60
+
+            [twitter]
+            consumer_key = ""
+            consumer_secret = ""
+            token = ""
+            secret = ""
+
+            [[mastodon]]
+            instance_url = "https://mastodon.social"
+
+            [[mastodon]]
+            instance_url = "https://fosstodon.org"
+
+            [webhook]
+            url = "https://example.com/hook"
+
+            [denylist]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.platform_names(),
+            vec![
+                "twitter",
+                "mastodon:mastodon.social",
+                "mastodon:fosstodon.org",
+                "webhook"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_post_interval() {
+        let config: Config = toml::from_str(
+            r#"
+            [interval]
+            post_ttl = 604800
+            fetch_interval = 60
+            post_interval = 60
+
+            [[mastodon]]
+            instance_url = "https://mastodon.social"
+            post_interval = 120
+
+            [bluesky]
+            host = "https://bsky.social"
+            identifier = "foo.bsky.social"
+            post_interval = 10
+
+            [denylist]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.post_interval(), 120);
+
+        let config: Config = toml::from_str(
+            r#"
+            [interval]
+            post_ttl = 604800
+            fetch_interval = 60
+            post_interval = 60
+
+            [bluesky]
+            host = "https://bsky.social"
+            identifier = "foo.bsky.social"
+            post_interval = 10
+
+            [denylist]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.post_interval(), 10);
+    }
+
+    #[test]
+    fn test_make_nostr_event() {
+        use secp256k1::{schnorr::Signature, KeyPair, Message, SECP256K1};
+        use sha2::{Digest, Sha256};
+
+        let keypair = KeyPair::from_seckey_str(
+            SECP256K1,
+            "0000000000000000000000000000000000000000000000000000000000000001",
+        )
+        .unwrap();
+        let event = make_nostr_event(&keypair, "hello", 1700000000).unwrap();
+        assert_eq!(
+            event.pubkey,
+            "79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798"
+        );
+        assert_eq!(event.kind, 1);
+
+        let serialized = format!(r#"[0,"{}",1700000000,1,[],"hello"]"#, event.pubkey);
+        let id = Sha256::digest(serialized.as_bytes());
+        assert_eq!(event.id, format!("{:x}", id));
+        assert_eq!(
+            event.id,
+            "bde202ea7642ff9910600c7edc948a1f4220f0cbf5e4fb2b7efafa681bbb5285"
+        );
+        // Signed with the reference implementation of BIP-340
+        assert_eq!(
+            event.sig,
+            "96ee7a4ebef51f83993655f363518c1676d9b0acee1473002955f0aaaeea8235\
+             01b0142575ed2c37a54c91a3f6d293b2ba279447948c83f71d4385d372be8d52"
+        );
+
+        let sig: Signature = event.sig.parse().unwrap();
+        let message = Message::from_slice(&id).unwrap();
+        let pubkey = keypair.x_only_public_key().0;
+        assert!(SECP256K1.verify_schnorr(&sig, &message, &pubkey).is_ok());
+
+        // Test vector 0 of BIP-340
+        let keypair = KeyPair::from_seckey_str(
+            SECP256K1,
+            "0000000000000000000000000000000000000000000000000000000000000003",
+        )
+        .unwrap();
+        assert_eq!(
+            keypair.x_only_public_key().0.to_string(),
+            "f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9"
+        );
+        assert_eq!(
+            sign_nostr_id(&keypair, &[0; 32]).unwrap().to_string(),
+            "e907831f80848d1069a5371b402410364bdf1c5f8307b0084c55f1ce2dca8215\
+             25f66a4a85ea8b71e482a74f382d2ce5ebeee8fdb2172f477df4900d310536c0"
+        );
+    }
+
+    #[test]
+    fn test_make_matrix_message() {
+        let repo = repo!("foo", "bar", "<b>bold</b> & more", 42);
+        let message = make_matrix_message(&repo, &FormatConfig::default());
+        assert_eq!(
+            message.body,
+            "foo / bar: <b>bold</b> & more ★42 https://github.com/foo/bar"
+        );
+        assert_eq!(
+            message.formatted_body,
+            "<a href=\"https://github.com/foo/bar\">foo / bar</a>: &lt;b&gt;bold&lt;/b&gt; &amp; more ★42"
+        );
+
+        let config: MatrixConfig = toml::from_str(
+            r#"
+            homeserver = "https://matrix.org"
+            room_id = "!abc:matrix.org"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            matrix_send_url(&config, "txn").unwrap().as_str(),
+            "https://matrix.org/_matrix/client/v3/rooms/!abc:matrix.org/send/m.room.message/txn"
+        );
+    }
+
+    #[test]
+    fn test_make_lemmy_post() {
+        let post = make_lemmy_post(&repo!("foo", "bar", "baz", 42), 7);
+        assert_eq!(
+            serde_json::to_value(&post).unwrap(),
+            serde_json::json!({
+                "name": "foo / bar",
+                "community_id": 7,
+                "url": "https://github.com/foo/bar",
+                "body": "baz",
+            })
+        );
+        let post = make_lemmy_post(&repo!("foo", "bar", "", 42), 7);
+        assert_eq!(post.body, None);
+    }
+
+    #[test]
+    fn test_make_reddit_submission() {
+        let config: RedditConfig = toml::from_str(
+            r#"
+            client_id = "id"
+            username = "foo"
+            subreddit = "rust"
+            "#,
+        )
+        .unwrap();
+        let submission = make_reddit_submission(&config, &repo!("foo", "bar", "baz", 42));
+        assert_eq!(
+            serde_json::to_value(&submission).unwrap(),
+            serde_json::json!({
+                "api_type": "json",
+                "kind": "link",
+                "sr": "rust",
+                "title": "foo / bar",
+                "url": "https://github.com/foo/bar",
+                "resubmit": true,
+            })
+        );
+    }
+
+    #[test]
+    fn test_reddit_submit_response() {
+        let response: RedditSubmitResponse = serde_json::from_str(
+            r#"{"json": {"errors": [], "data": {"url": "https://www.reddit.com/r/rust/comments/abc/foo_bar/"}}}"#,
+        )
+        .unwrap();
+        assert!(response.into_result().is_ok());
+
+        let response: RedditSubmitResponse = serde_json::from_str(
+            r#"{"json": {"errors": [["RATELIMIT", "Take a break for 9 minutes", "ratelimit"]]}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            response.into_result().unwrap_err().to_string(),
+            "Reddit rejected the post: RATELIMIT: Take a break for 9 minutes: ratelimit"
+        );
+    }
+
+    #[test]
+    fn test_make_slack_body() {
+        let repo = repo!("foo", "bar", "Fast & <small>", 42);
+        let body = make_slack_body(&repo, &FormatConfig::default());
+        assert_eq!(
+            body["blocks"][0]["text"]["text"],
+            "*<https://github.com/foo/bar|foo / bar>*\nFast &amp; &lt;small&gt;"
+        );
+        assert_eq!(body["blocks"][1]["elements"][0]["text"], "★42");
+    }
+
+    #[test]
+    fn test_is_language_tag() {
+        assert!(is_language_tag("en"));
+        assert!(is_language_tag("ko"));
+        assert!(is_language_tag("zh-Hant-TW"));
+        assert!(is_language_tag("es-419"));
+        assert!(!is_language_tag(""));
+        assert!(!is_language_tag("e"));
+        assert!(!is_language_tag("en_US"));
+        assert!(!is_language_tag("en-"));
+    }
+
+    #[test]
+    fn test_next_scheduled_at() {
+        let start = time::macros::time!(09:00);
+        let end = time::macros::time!(17:00);
+        let now = time::macros::datetime!(2023-06-01 12:00 UTC);
+        let next = |previous, now| next_scheduled_at(start, end, 8, previous, now);
+
+        // At least 5 minutes from now, as Mastodon requires
+        assert_eq!(
+            next(None, now),
+            Some(time::macros::datetime!(2023-06-01 12:05 UTC))
+        );
+        assert_eq!(
+            next(Some(time::macros::datetime!(2023-06-01 11:00 UTC)), now),
+            Some(time::macros::datetime!(2023-06-01 12:05 UTC))
+        );
+        // An eighth of the window apart
+        assert_eq!(
+            next(Some(time::macros::datetime!(2023-06-01 13:00 UTC)), now),
+            Some(time::macros::datetime!(2023-06-01 14:00 UTC))
+        );
+        // Before the window
+        assert_eq!(
+            next(None, time::macros::datetime!(2023-06-01 06:00 UTC)),
+            Some(time::macros::datetime!(2023-06-01 09:00 UTC))
+        );
+        // The window of today is full, and the next one is not scheduled until today's ends
+        assert_eq!(
+            next(Some(time::macros::datetime!(2023-06-01 16:00 UTC)), now),
+            None
+        );
+        assert_eq!(
+            next(
+                Some(time::macros::datetime!(2023-06-01 16:00 UTC)),
+                time::macros::datetime!(2023-06-01 18:00 UTC)
+            ),
+            Some(time::macros::datetime!(2023-06-02 09:00 UTC))
+        );
+        // At most 8 in the window of a day
+        let mut previous = None;
+        let mut scheduled = 0;
+        let now = time::macros::datetime!(2023-06-01 18:00 UTC);
+        while let Some(at) = next(previous, now) {
+            assert_eq!(at.date(), time::macros::date!(2023 - 06 - 02));
+            previous = Some(at);
+            scheduled += 1;
+        }
+        assert_eq!(scheduled, 8);
+    }
+
+    #[test]
+    fn test_is_bluesky_identifier() {
+        assert!(is_bluesky_identifier("alice.bsky.social"));
+        assert!(is_bluesky_identifier("rust-trending.example.com"));
+        assert!(is_bluesky_identifier("did:plc:z72i7hdynmk6r22z27h6tvur"));
+        assert!(is_bluesky_identifier("alice@example.com"));
+        assert!(!is_bluesky_identifier("@alice.bsky.social"));
+        assert!(!is_bluesky_identifier("alice"));
+        assert!(!is_bluesky_identifier("alice.bsky.social "));
+        assert!(!is_bluesky_identifier("did:plc"));
+    }
+
+    #[test]
+    fn test_bluesky_login_hint() {
+        let error = |status: u16, error: &str| {
+            atrium_xrpc::error::Error::XrpcResponse(atrium_xrpc::error::XrpcError {
+                status: hyper::http::StatusCode::from_u16(status).unwrap(),
+                error: Some(atrium_xrpc::error::XrpcErrorKind::Undefined(
+                    atrium_xrpc::error::ErrorResponseBody {
+                        error: Some(error.to_string()),
+                        message: None,
+                    },
+                )),
+            })
+        };
+        assert!(bluesky_login_hint(&error(401, "AuthFactorTokenRequired"))
+            .unwrap()
+            .contains("app password"));
+        assert!(bluesky_login_hint(&error(401, "AuthenticationRequired"))
+            .unwrap()
+            .contains("bluesky.identifier"));
+        assert!(bluesky_login_hint(&error(500, "InternalServerError")).is_none());
+    }
+
+    #[test]
+    fn test_detect_description_lang() {
+        assert_eq!(
+            detect_description_lang(
+                "Empowering everyone to build reliable and efficient software."
+            ),
+            Some(whatlang::Lang::Eng)
+        );
+        assert_eq!(
+            detect_description_lang("Быстрый и надёжный веб-сервер, написанный на языке Rust"),
+            Some(whatlang::Lang::Rus)
+        );
+        assert_eq!(detect_description_lang("A GPU terminal"), None);
+        assert_eq!(detect_description_lang(""), None);
+    }
+
+    #[test]
+    fn test_escape_mentions() {
+        let format = FormatConfig::default();
+        assert_eq!(escape_mentions("@foo", &format), "﹫foo");
+        assert_eq!(escape_mentions("by @foo_bar.", &format), "by ﹫foo_bar.");
+        assert_eq!(escape_mentions("(@foo)", &format), "(﹫foo)");
+        assert_eq!(escape_mentions("foo@bar.com", &format), "foo@bar.com");
+        assert_eq!(escape_mentions("trailing @", &format), "trailing @");
+        assert_eq!(escape_mentions("@ foo", &format), "@ foo");
+
+        let format: FormatConfig = toml::from_str(r#"at_replacement = "(at)""#).unwrap();
+        assert_eq!(escape_mentions("by @foo.", &format), "by (at)foo.");
+        assert_eq!(escape_mentions("foo@bar.com", &format), "foo@bar.com");
+
+        let format: FormatConfig = toml::from_str(r#"at_escape = "zero_width_space""#).unwrap();
+        assert_eq!(escape_mentions("by @foo.", &format), "by @\u{200B}foo.");
+        assert_eq!(escape_mentions("foo@bar.com", &format), "foo@bar.com");
+
+        let repo = repo!("foo", "bar", "by @foo", 42);
+        assert!(
+            make_toot(&repo, DEFAULT_TEMPLATE, "", None, &FormatConfig::default())
+                .contains("by ﹫foo")
+        );
+        assert!(
+            make_bluesky_post(&repo, DEFAULT_TEMPLATE, "", &FormatConfig::default())
+                .contains("by @foo")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_max_posts_per_run() {
+        use wiremock::matchers::{method, path};
+
+        let github = wiremock::MockServer::start().await;
+        wiremock::Mock::given(method("GET"))
+            .and(path("/trending/rust"))
+            .respond_with(wiremock::ResponseTemplate::new(200).set_body_string(TEST_HTML))
+            .mount(&github)
+            .await;
+        let webhook = wiremock::MockServer::start().await;
+        wiremock::Mock::given(method("POST"))
+            .and(path("/webhook"))
+            .respond_with(wiremock::ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&webhook)
+            .await;
+
+        let (mut config, _) = parse_config(&format!(
+            r#"
+            [interval]
+            post_ttl = 3600
+            fetch_interval = 60
+            post_interval = 3600
+
+            [trending]
+            max_posts_per_run = 1
+
+            [github]
+            host = "{}"
+
+            [webhook]
+            url = "{}/webhook"
+
+            [denylist]
+            "#,
+            github.uri(),
+            webhook.uri()
+        ))
+        .unwrap();
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        let mut store = SqliteStore::new(conn).unwrap();
+
+        // Returns right after the last post, rather than waiting for post_interval
+        let mut summary = LoopSummary::default();
+        tokio::time::timeout(
+            Duration::from_secs(10),
+            post_trending(&config, &mut store, "rust", Since::Daily, &mut summary),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert_eq!(summary.repos_posted, 1);
+
+        // Posts logged in dry-run mode count as well
+        config.dry_run = true;
+        config.trending.max_posts_per_run = Some(2);
+        let mut summary = LoopSummary::default();
+        post_trending(&config, &mut store, "rust", Since::Daily, &mut summary)
+            .await
+            .unwrap();
+        assert_eq!(summary.repos_posted, 2);
+        assert_eq!(summary.already_posted, 1);
+    }
+
+    #[tokio::test]
+    async fn test_backfill_descriptions() {
+        use wiremock::matchers::{method, path};
+
+        let server = wiremock::MockServer::start().await;
+        let repository = |name: &str, description: Option<&str>| {
+            serde_json::json!({
+                "name": name,
+                "owner": {"login": "foo"},
+                "description": description,
+                "stargazers_count": 42,
+                "forks_count": 0,
+                "language": "Rust",
+                "created_at": null,
+            })
+        };
+        wiremock::Mock::given(method("GET"))
+            .and(path("/api/v3/repos/foo/bar"))
+            .respond_with(
+                wiremock::ResponseTemplate::new(200)
+                    .set_body_json(repository("bar", Some("From the API"))),
+            )
+            .expect(2)
+            .mount(&server)
+            .await;
+        // Not fetched again in the next loop
+        wiremock::Mock::given(method("GET"))
+            .and(path("/api/v3/repos/foo/empty"))
+            .respond_with(
+                wiremock::ResponseTemplate::new(200).set_body_json(repository("empty", None)),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let config: GithubConfig = toml::from_str(&format!(
+            r#"
+            host = "{}"
+            backfill_descriptions = true
+            "#,
+            server.uri()
+        ))
+        .unwrap();
+        for _ in 0..2 {
+            let mut repos = vec![
+                repo!("foo", "bar", "", 42),
+                repo!("foo", "empty", " ", 42),
+                repo!("foo", "baz", "From the trending page", 42),
+            ];
+            backfill_descriptions(&config, &mut repos).await;
+            assert_eq!(repos[0].description, "From the API");
+            assert_eq!(repos[1].description, " ");
+            assert_eq!(repos[2].description, "From the trending page");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_toot() {
+        use wiremock::matchers::{
+            body_string, body_string_contains, header, header_exists, method, path,
+        };
+
+        let server = wiremock::MockServer::start().await;
+        // Mastodon takes form bodies, unlike the JSON of most other platforms
+        wiremock::Mock::given(method("POST"))
+            .and(path("/api/v1/statuses"))
+            .and(header("authorization", "Bearer token"))
+            .and(header("content-type", "application/x-www-form-urlencoded"))
+            .and(header_exists("idempotency-key"))
+            .and(body_string(
+                "status=foo%2Fbar+%26+baz&visibility=unlisted&spoiler_text=cw",
+            ))
+            .respond_with(
+                wiremock::ResponseTemplate::new(200).set_body_json(serde_json::json!({"id": "1"})),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        wiremock::Mock::given(method("POST"))
+            .and(path("/api/v1/statuses"))
+            .and(body_string_contains("in_reply_to_id=1"))
+            .respond_with(
+                wiremock::ResponseTemplate::new(200).set_body_json(serde_json::json!({"id": "2"})),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let config: MastodonConfig = toml::from_str(&format!(
+            r#"
+            instance_url = "{}/"
+            access_token = "token"
+            content_warning = "cw"
+            "#,
+            server.uri()
+        ))
+        .unwrap();
+        let client = reqwest::Client::new();
+        let id = toot(
+            &client,
+            &config,
+            &repo!("foo", "bar", "", 0),
+            "foo/bar & baz",
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(id, "1");
+        let id = toot(
+            &client,
+            &config,
+            &repo!("foo", "baz", "", 0),
+            "foo/baz",
+            Some(&id),
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(id, "2");
+    }
+
+    #[tokio::test]
+    async fn test_post_slack() {
+        use wiremock::matchers::{body_json, header, method, path};
+
+        let server = wiremock::MockServer::start().await;
+        let repo = repo!("foo", "bar", "baz", 1);
+        let format = FormatConfig::default();
+        wiremock::Mock::given(method("POST"))
+            .and(path("/services/webhook"))
+            .and(header("content-type", "application/json"))
+            .and(body_json(make_slack_body(&repo, &format)))
+            .respond_with(wiremock::ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let config: SlackConfig = toml::from_str(&format!(
+            r#"webhook_url = "{}/services/webhook""#,
+            server.uri()
+        ))
+        .unwrap();
+        post_slack(&reqwest::Client::new(), &config, &repo, &format)
+            .await
+            .unwrap();
+    }
+}
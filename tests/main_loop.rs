@@ -0,0 +1,102 @@
+//! Runs whole passes of the bot against a mock trending page and a mock webhook, keeping posted
+//! repos in SQLite.
+
+use std::path::PathBuf;
+
+use clap::Parser;
+use wiremock::{
+    matchers::{method, path, query_param},
+    Mock, MockServer, ResponseTemplate,
+};
+
+const TEST_HTML: &str = include_str!("../testdata/test.html");
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("rust-trending-{}-{}", std::process::id(), name))
+}
+
+#[tokio::test]
+async fn test_run_once() {
+    let github = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/trending/rust"))
+        .and(query_param("since", "daily"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(TEST_HTML))
+        .expect(2)
+        .mount(&github)
+        .await;
+    let webhook = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/webhook"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(4)
+        .mount(&webhook)
+        .await;
+
+    let sqlite_path = temp_path("posted.db");
+    let config_path = temp_path("config.toml");
+    let _ = std::fs::remove_file(&sqlite_path);
+    std::fs::write(
+        &config_path,
+        format!(
+            r#"
+            [interval]
+            post_ttl = 3600
+            fetch_interval = 60
+            post_interval = 1
+
+            [trending]
+            max_posts_per_run = 2
+
+            [github]
+            host = "{}"
+
+            [sqlite]
+            path = "{}"
+
+            [webhook]
+            url = "{}/webhook"
+
+            [denylist]
+            "#,
+            github.uri(),
+            sqlite_path.display(),
+            webhook.uri()
+        ),
+    )
+    .unwrap();
+    let args = || {
+        rust_trending::Args::parse_from([
+            "rust-trending",
+            "--once",
+            "--config",
+            config_path.to_str().unwrap(),
+        ])
+    };
+
+    let posted_names = || async {
+        webhook
+            .received_requests()
+            .await
+            .unwrap()
+            .iter()
+            .map(|request| {
+                let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+                body["repo"]["name"].as_str().unwrap().to_string()
+            })
+            .collect::<Vec<_>>()
+    };
+
+    rust_trending::run(args()).await.unwrap();
+    assert_eq!(posted_names().await, vec!["servo", "vector"]);
+
+    // Posted repos are skipped in the next pass, so the next ones are posted instead
+    rust_trending::run(args()).await.unwrap();
+    assert_eq!(
+        posted_names().await,
+        vec!["servo", "vector", "rust", "wasmer"]
+    );
+
+    std::fs::remove_file(&sqlite_path).unwrap();
+    std::fs::remove_file(&config_path).unwrap();
+}